@@ -0,0 +1,1309 @@
+use std::{
+    mem,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use ash::{
+    version::{DeviceV1_0, InstanceV1_0},
+    vk, Device,
+};
+
+use cgmath::{Deg, Matrix4, Point3, Vector3};
+
+use winit::{
+    dpi::PhysicalSize,
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    platform::desktop::EventLoopExtDesktop,
+    window::Window,
+};
+
+use crate::config::{self, AssetConfig, ClampedSetting, Config, PresentMode};
+#[cfg(feature = "shader-compiler")]
+use crate::setup::shader_compiler::{ShaderCompiler, ShaderWatcher};
+use crate::setup::{
+    self,
+    allocator::{Allocator, HeapStats},
+    buffer::Buffer,
+    command_buffers::{DrawList, RecordInfo},
+    command_workers::CommandWorkers,
+    context::VulkanContext,
+    error::{SetupError, SetupResult, VkResultExt},
+    frame_sync::FrameSyncData,
+    graphics_pipeline::{PipelineKind, ShaderCode},
+    image::Image,
+    material::MaterialResources,
+    model::NormalGeneration,
+    offscreen::OffscreenData,
+    pipeline_cache::PipelineCache,
+    queue::{Batch, Queue, Timeline},
+    render_target::RenderTarget,
+    swapchain::SwapchainData,
+};
+use crate::structs::{Mesh, SubMesh, TextureSource, UBO};
+
+/// Source of the animation time fed into the uniform buffers; `Fixed` makes frames reproducible.
+pub enum FrameClock {
+    Realtime(Instant),
+    Fixed(f32),
+}
+
+impl FrameClock {
+    pub fn elapsed_seconds(&self) -> f32 {
+        match self {
+            FrameClock::Realtime(init_timestamp) => init_timestamp.elapsed().as_secs_f32(),
+            FrameClock::Fixed(elapsed_seconds) => *elapsed_seconds,
+        }
+    }
+}
+
+/// Per-frame state handed to a `DrawCallback`.
+pub struct FrameInfo<'a> {
+    /// Frame slot, below the number of frames in flight.
+    pub frame_index: usize,
+    pub elapsed_seconds: f32,
+    /// The loaded model's sub-meshes, which `Draw::sub_mesh` indexes.
+    pub sub_meshes: &'a [SubMesh],
+    /// CPU time spent recording the previous frame's command buffers.
+    pub last_recording_time: Duration,
+}
+
+/// Builds the draw list of every frame, starting from an empty list on the default clear color.
+pub type DrawCallback = Box<dyn FnMut(&mut DrawList, &FrameInfo)>;
+
+const SCREENSHOT_KEY: VirtualKeyCode = VirtualKeyCode::F12;
+/// Steps through the supported MSAA sample counts, wrapping around to 1x.
+const MSAA_KEY: VirtualKeyCode = VirtualKeyCode::M;
+const VSYNC_KEY: VirtualKeyCode = VirtualKeyCode::V;
+const WIREFRAME_KEY: VirtualKeyCode = VirtualKeyCode::W;
+
+pub struct VulkanApp {
+    device: Arc<Device>,
+
+    render_target: RenderTarget,
+    render_pass: vk::RenderPass,
+    /// Color format and sample count `render_pass` and `pipelines` were built for; attachments
+    /// matching both keep them compatible, so they survive swapchain recreation.
+    render_pass_compatibility: (vk::Format, vk::SampleCountFlags),
+
+    /// Indexed by `PipelineKind`.
+    pipelines: Vec<vk::Pipeline>,
+    pipeline_layout: vk::PipelineLayout,
+    /// Whether the vertex shader reads the draws' transforms as push constants.
+    push_object_transforms: bool,
+    pipeline_cache: PipelineCache,
+    /// What `pipelines` were built from.
+    vertex_shader: ShaderCode,
+    fragment_shader: ShaderCode,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    /// Reflected from the shaders; only these bindings are allocated and written.
+    descriptor_bindings: Vec<vk::DescriptorSetLayoutBinding>,
+
+    framebuffers: Vec<vk::Framebuffer>,
+
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    mesh: Mesh,
+
+    uniform_buffers: Vec<Buffer>,
+
+    descriptor_pool: vk::DescriptorPool,
+    /// Indexed `[image][material]`.
+    descriptor_sets: Vec<Vec<vk::DescriptorSet>>,
+
+    /// For uploads and readbacks.
+    command_pool: vk::CommandPool,
+    /// Reset every time their frame slot comes around.
+    frame_command_pools: Vec<vk::CommandPool>,
+    /// Indexed by frame slot, from the matching pool in `frame_command_pools`.
+    command_buffers: Vec<vk::CommandBuffer>,
+    draw_list: DrawList,
+    draw_callback: Option<DrawCallback>,
+    last_recording_time: Duration,
+    /// Draws the model with `PipelineKind::Wireframe` when there is no draw callback.
+    wireframe: bool,
+    frame_sync_data: FrameSyncData,
+
+    graphics_queue: Queue,
+    present_queue: Option<vk::Queue>,
+
+    materials: Vec<MaterialResources>,
+    /// `None` at 1x MSAA.
+    color_image: Option<Image>,
+    depth_image: Image,
+
+    msaa_samples: vk::SampleCountFlags,
+    frames_in_flight: usize,
+    present_modes: Vec<PresentMode>,
+    vsync: bool,
+    clamped_settings: Vec<ClampedSetting>,
+    /// Shader sources to recompile when they change.
+    #[cfg(feature = "shader-compiler")]
+    assets: AssetConfig,
+
+    /// Records the draw list into secondary command buffers when `recording_threads` is set;
+    /// joined on drop, before the device is destroyed.
+    command_workers: Option<CommandWorkers>,
+
+    // fields are dropped in declaration order: the context must outlive every resource above
+    allocator: Arc<Allocator>,
+    context: VulkanContext,
+}
+
+impl VulkanApp {
+    /// Settings the device cannot provide are clamped; see `clamped_settings`.
+    pub fn new(window: &Window, config: &Config) -> SetupResult<Self> {
+        Self::create(Some(window), window.inner_size(), config)
+    }
+
+    /// Renders into offscreen images instead of a swapchain; needs neither a window, a surface
+    /// nor a present queue, so it also runs on CPU implementations such as lavapipe.
+    pub fn new_headless(image_extent: vk::Extent2D, config: &Config) -> SetupResult<Self> {
+        let physical_size = PhysicalSize::new(image_extent.width, image_extent.height);
+        Self::create(None, physical_size, config)
+    }
+
+    fn create(
+        window: Option<&Window>,
+        physical_size: PhysicalSize<u32>,
+        config: &Config,
+    ) -> SetupResult<Self> {
+        let Config {
+            assets, graphics, ..
+        } = config;
+        let mut clamped_settings = vec![];
+
+        let context =
+            VulkanContext::new(window, graphics.validation, graphics.timeline_semaphores)?;
+        let instance = &context.instance;
+        let physical_device = context.physical_device;
+        let device = Arc::clone(&context.device);
+        let allocator = Arc::new(Allocator::new(instance, physical_device, &device));
+        let limits = unsafe { instance.get_physical_device_properties(physical_device) }.limits;
+
+        if context.validation_enabled != graphics.validation {
+            clamped_settings.push(ClampedSetting::new(
+                "validation",
+                graphics.validation,
+                context.validation_enabled,
+            ));
+        }
+        let timeline_semaphores = context.timeline_support.is_some();
+        if timeline_semaphores != graphics.timeline_semaphores {
+            clamped_settings.push(ClampedSetting::new(
+                "timeline_semaphores",
+                graphics.timeline_semaphores,
+                timeline_semaphores,
+            ));
+        }
+
+        let msaa_samples = setup::devices::physical::clamp_sample_count(
+            context.msaa_sample_counts,
+            graphics.msaa_samples,
+        );
+        if msaa_samples.as_raw() != graphics.msaa_samples {
+            clamped_settings.push(ClampedSetting::new(
+                "msaa_samples",
+                graphics.msaa_samples,
+                msaa_samples.as_raw(),
+            ));
+        }
+
+        let max_anisotropy = graphics.clamped_anisotropy(limits.max_sampler_anisotropy);
+        if max_anisotropy != graphics.anisotropy {
+            clamped_settings.push(ClampedSetting::new(
+                "anisotropy",
+                graphics.anisotropy,
+                max_anisotropy,
+            ));
+        }
+
+        let present_mode_preferences =
+            config::present_mode_preferences(&graphics.present_modes, graphics.vsync);
+        let render_target = match context.surface_pair() {
+            Some((surface, surface_khr)) => {
+                let swapchain_data = SwapchainData::new(
+                    instance,
+                    physical_device,
+                    &device,
+                    surface,
+                    surface_khr,
+                    physical_size,
+                    &present_mode_preferences,
+                    vk::SwapchainKHR::null(),
+                )?;
+                if !present_mode_preferences.contains(&swapchain_data.present_mode) {
+                    clamped_settings.push(ClampedSetting::new(
+                        "present_modes",
+                        &present_mode_preferences,
+                        swapchain_data.present_mode,
+                    ));
+                }
+                RenderTarget::Swapchain(swapchain_data)
+            }
+            None => RenderTarget::Offscreen(OffscreenData::new(
+                instance,
+                physical_device,
+                &allocator,
+                vk::Extent2D {
+                    width: physical_size.width,
+                    height: physical_size.height,
+                },
+                graphics.frames_in_flight.max(1),
+            )?),
+        };
+
+        // a frame in flight beyond the image count would resubmit a pending command buffer
+        let frames_in_flight = graphics.clamped_frames_in_flight(render_target.images().len());
+        if frames_in_flight != graphics.frames_in_flight {
+            clamped_settings.push(ClampedSetting::new(
+                "frames_in_flight",
+                graphics.frames_in_flight,
+                frames_in_flight,
+            ));
+        }
+        let render_pass = setup::render_pass::create(
+            instance,
+            &device,
+            &physical_device,
+            &render_target,
+            msaa_samples,
+        )?;
+        let command_pool = setup::command_pool::create(&device, &context.queue_family_indices)?;
+
+        let (vertex_shader, fragment_shader) = load_shaders(assets);
+        let shader_interfaces =
+            setup::graphics_pipeline::reflect_shaders(&vertex_shader, &fragment_shader)?;
+        let descriptor_bindings =
+            setup::shader_reflection::descriptor_set_layout_bindings(&shader_interfaces)?;
+        let descriptor_set_layout =
+            setup::uniform_buffers::create_descriptor_set_layout(&device, &descriptor_bindings)?;
+        let pipeline_cache = PipelineCache::load(
+            instance,
+            physical_device,
+            &device,
+            graphics.pipeline_cache.as_deref(),
+        )?;
+        let pipeline_layout = setup::graphics_pipeline::create_layout(
+            &device,
+            &descriptor_set_layout,
+            &setup::shader_reflection::push_constant_ranges(&shader_interfaces),
+        )?;
+        let push_object_transforms =
+            setup::command_buffers::reads_object_transform(&shader_interfaces[0]);
+        let pipelines = setup::graphics_pipeline::create(
+            &device,
+            render_pass,
+            pipeline_layout,
+            pipeline_cache.cache,
+            msaa_samples,
+            &unsafe { instance.get_physical_device_features(physical_device) },
+            &vertex_shader,
+            &fragment_shader,
+        )?;
+        let graphics_queue = Queue {
+            handle: unsafe { device.get_device_queue(context.queue_family_indices.graphics, 0) },
+            timeline: context
+                .timeline_support
+                .map(|support| Timeline::new(&context.entry, instance, &device, support))
+                .transpose()?,
+        };
+        let present_queue = context
+            .queue_family_indices
+            .present
+            .map(|present| unsafe { device.get_device_queue(present, 0) });
+
+        let color_image = setup::image::create_color_resources(
+            &allocator,
+            render_target.image_extent(),
+            render_target.image_format(),
+            msaa_samples,
+        )?;
+
+        let depth_image = setup::image::create_depth_resources(
+            instance,
+            &allocator,
+            &physical_device,
+            render_target.image_extent(),
+            msaa_samples,
+        )?;
+
+        let framebuffers = setup::framebuffers::create(
+            &device,
+            &render_target,
+            render_pass,
+            color_image.as_ref().map(|color_image| &color_image.view),
+            &depth_image.view,
+        )?;
+
+        let mut mesh = setup::model::load(&assets.model_path(), NormalGeneration::Smooth)?;
+        if let Some(texture_path) = assets.texture_path() {
+            mesh.materials
+                .iter_mut()
+                .filter(|material| material.diffuse_texture.is_none())
+                .for_each(|material| {
+                    material.diffuse_texture = Some(TextureSource::File(texture_path.clone()))
+                });
+        }
+        let materials = mesh
+            .materials
+            .iter()
+            .map(|material| {
+                setup::material::create(
+                    instance,
+                    &allocator,
+                    &physical_device,
+                    command_pool,
+                    &graphics_queue,
+                    material,
+                    max_anisotropy,
+                )
+            })
+            .collect::<SetupResult<Vec<MaterialResources>>>()?;
+        let vertex_buffer = setup::vertex_buffer::create(
+            &allocator,
+            command_pool,
+            &graphics_queue,
+            &mesh.vertices,
+        )?;
+        let index_buffer =
+            setup::index_buffer::create(&allocator, command_pool, &graphics_queue, &mesh.indices)?;
+
+        let uniform_buffers = setup::uniform_buffers::create(&allocator, &render_target.images())?;
+        let descriptor_pool = setup::uniform_buffers::create_descriptor_pool(
+            &device,
+            &descriptor_bindings,
+            &render_target.images(),
+            materials.len(),
+        )?;
+        let descriptor_sets = setup::uniform_buffers::create_descriptor_sets(
+            &device,
+            descriptor_pool,
+            descriptor_set_layout,
+            &descriptor_bindings,
+            &uniform_buffers,
+            &render_target.images(),
+            &materials,
+        )?;
+
+        let frame_command_pools = setup::command_pool::create_per_frame(
+            &device,
+            &context.queue_family_indices,
+            frames_in_flight,
+        )?;
+        let command_buffers = frame_command_pools
+            .iter()
+            .map(|command_pool| {
+                setup::command_buffers::allocate(
+                    &device,
+                    *command_pool,
+                    vk::CommandBufferLevel::PRIMARY,
+                    1,
+                )
+                .map(|command_buffers| command_buffers[0])
+            })
+            .collect::<SetupResult<Vec<vk::CommandBuffer>>>()?;
+
+        let command_workers = match graphics.recording_threads {
+            0 => None,
+            recording_threads => Some(CommandWorkers::new(
+                &device,
+                &context.queue_family_indices,
+                frames_in_flight,
+                recording_threads,
+                &mesh.sub_meshes,
+            )?),
+        };
+
+        let frame_sync_data = setup::frame_sync::create(
+            &device,
+            &graphics_queue,
+            frames_in_flight,
+            render_target.images().len(),
+        )?;
+
+        Ok(Self {
+            device,
+            render_pass_compatibility: (render_target.image_format(), msaa_samples),
+            render_target,
+            render_pass,
+            pipelines,
+            pipeline_layout,
+            push_object_transforms,
+            pipeline_cache,
+            vertex_shader,
+            fragment_shader,
+            descriptor_set_layout,
+            descriptor_bindings,
+            framebuffers,
+            vertex_buffer,
+            index_buffer,
+            uniform_buffers,
+            descriptor_pool,
+            descriptor_sets,
+            command_pool,
+            frame_command_pools,
+            command_buffers,
+            draw_list: DrawList::default(),
+            draw_callback: None,
+            last_recording_time: Duration::default(),
+            wireframe: false,
+            frame_sync_data,
+            graphics_queue,
+            present_queue,
+            mesh,
+            materials,
+            color_image,
+            depth_image,
+            msaa_samples,
+            frames_in_flight,
+            present_modes: graphics.present_modes.clone(),
+            vsync: graphics.vsync,
+            clamped_settings,
+            #[cfg(feature = "shader-compiler")]
+            assets: assets.clone(),
+            command_workers,
+            allocator,
+            context,
+        })
+    }
+
+    /// Runs the event loop until the window is closed or a frame fails to render; the error that
+    /// stopped the loop is returned. Nothing is drawn while the window is minimized.
+    pub fn run(&mut self, event_loop: &mut EventLoop<()>, window: Window) -> SetupResult<()> {
+        let mut physical_window_size = window.inner_size();
+        let mut current_frame: usize = 0;
+        let mut framebuffer_resized = false;
+        let mut screenshot_requested = false;
+        let clock = FrameClock::Realtime(Instant::now());
+        let mut result = Ok(());
+        #[cfg(feature = "shader-compiler")]
+        let mut shader_reload = self.watch_shader_sources();
+
+        event_loop.run_return(|event, _, control_flow| {
+            match event {
+                Event::WindowEvent { event, .. } => match event {
+                    WindowEvent::Resized(new_physical_window_size) => {
+                        framebuffer_resized = true;
+                        physical_window_size = new_physical_window_size;
+                        *control_flow = ControlFlow::Poll;
+                    }
+                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode: Some(SCREENSHOT_KEY),
+                                state: ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    } => {
+                        screenshot_requested = true;
+                        *control_flow = ControlFlow::Poll;
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode: Some(MSAA_KEY),
+                                state: ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    } => {
+                        match self.set_msaa_samples(self.next_msaa_samples()) {
+                            Ok(msaa_samples) => println!("MSAA set to {}x", msaa_samples.as_raw()),
+                            Err(error) => {
+                                result = Err(error);
+                                *control_flow = ControlFlow::Exit;
+                                return;
+                            }
+                        }
+                        *control_flow = ControlFlow::Poll;
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode: Some(VSYNC_KEY),
+                                state: ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    } => {
+                        match self.set_vsync(!self.vsync) {
+                            Ok(Some(present_mode)) => {
+                                println!("Present mode set to {:?}", present_mode)
+                            }
+                            Ok(None) => {}
+                            Err(error) => {
+                                result = Err(error);
+                                *control_flow = ControlFlow::Exit;
+                                return;
+                            }
+                        }
+                        *control_flow = ControlFlow::Poll;
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode: Some(WIREFRAME_KEY),
+                                state: ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    } => {
+                        self.wireframe = !self.wireframe;
+                        *control_flow = ControlFlow::Poll;
+                    }
+                    _ => *control_flow = ControlFlow::Poll,
+                },
+                _ => *control_flow = ControlFlow::Poll,
+            }
+            if *control_flow == ControlFlow::Exit {
+                return;
+            }
+            // a minimized window has no extent to create a swapchain for; sleep until it is restored
+            if physical_window_size.width == 0 || physical_window_size.height == 0 {
+                *control_flow = ControlFlow::Wait;
+                return;
+            }
+            #[cfg(feature = "shader-compiler")]
+            if let Some((compiler, watcher)) = shader_reload.as_mut() {
+                let changed_sources = watcher.changed_sources();
+                if !changed_sources.is_empty() {
+                    if let Err(error) = self.reload_shaders(compiler, &changed_sources) {
+                        result = Err(error);
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                }
+            }
+            if let Err(error) = self.draw_frame(
+                current_frame,
+                &physical_window_size,
+                &mut framebuffer_resized,
+                &mut screenshot_requested,
+                &clock,
+            ) {
+                result = Err(error);
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+            current_frame = (current_frame + 1) % self.frames_in_flight;
+        });
+
+        self.device_wait_idle()?;
+        result
+    }
+
+    /// Returns the index of the offscreen image holding the last rendered frame.
+    pub fn run_headless(&mut self, frame_count: usize, clock: &FrameClock) -> SetupResult<usize> {
+        for frame in 0..frame_count {
+            self.draw_frame_headless(frame % self.frames_in_flight, clock)?;
+        }
+        self.device_wait_idle()?;
+        Ok((frame_count.max(1) - 1) % self.frames_in_flight)
+    }
+
+    /// Copies a resolved render target image back to the host; rendering into it must have completed.
+    pub fn read_back_frame(&self, image_index: usize) -> SetupResult<image::RgbaImage> {
+        setup::readback::read_image(
+            &self.allocator,
+            self.command_pool,
+            &self.graphics_queue,
+            self.render_target.images()[image_index],
+            self.render_target.final_layout(),
+            self.render_target.image_format(),
+            self.render_target.image_extent(),
+        )
+    }
+
+    pub fn msaa_samples(&self) -> vk::SampleCountFlags {
+        self.msaa_samples
+    }
+
+    /// Switches to the highest supported sample count not above `requested` and rebuilds the
+    /// attachments, render pass, framebuffers and pipelines for it. Returns the count in use.
+    pub fn set_msaa_samples(&mut self, requested: u32) -> SetupResult<vk::SampleCountFlags> {
+        let msaa_samples = setup::devices::physical::clamp_sample_count(
+            self.context.msaa_sample_counts,
+            requested,
+        );
+        if msaa_samples != self.msaa_samples {
+            self.msaa_samples = msaa_samples;
+            let image_extent = self.render_target.image_extent();
+            unsafe {
+                self.recreate_swapchain(&PhysicalSize::new(
+                    image_extent.width,
+                    image_extent.height,
+                ))?;
+            }
+        }
+        Ok(msaa_samples)
+    }
+
+    /// The next supported sample count above the current one, or 1 after the highest.
+    fn next_msaa_samples(&self) -> u32 {
+        setup::devices::physical::next_sample_count(
+            self.context.msaa_sample_counts,
+            self.msaa_samples,
+        )
+        .as_raw()
+    }
+
+    /// The present mode in use, or `None` when rendering offscreen.
+    pub fn present_mode(&self) -> Option<vk::PresentModeKHR> {
+        self.render_target
+            .swapchain_data()
+            .map(|swapchain_data| swapchain_data.present_mode)
+    }
+
+    /// Turns vsync on or off, recreating the swapchain with the newly preferred present mode.
+    /// Returns the mode in use, or `None` when rendering offscreen.
+    pub fn set_vsync(&mut self, vsync: bool) -> SetupResult<Option<vk::PresentModeKHR>> {
+        if vsync != self.vsync {
+            self.vsync = vsync;
+            if self.render_target.swapchain_data().is_some() {
+                let image_extent = self.render_target.image_extent();
+                unsafe {
+                    self.recreate_swapchain(&PhysicalSize::new(
+                        image_extent.width,
+                        image_extent.height,
+                    ))?;
+                }
+            }
+        }
+        Ok(self.present_mode())
+    }
+
+    /// Replaces the default draw list, the whole model with the opaque or wireframe pipeline;
+    /// the callback runs for every frame, so changes show up in the next one.
+    pub fn set_draw_callback<F>(&mut self, draw_callback: F)
+    where
+        F: FnMut(&mut DrawList, &FrameInfo) + 'static,
+    {
+        self.draw_callback = Some(Box::new(draw_callback));
+    }
+
+    /// Switches the default draw list between the opaque and wireframe pipelines.
+    pub fn set_wireframe(&mut self, wireframe: bool) {
+        self.wireframe = wireframe;
+    }
+
+    /// Settings that were replaced with the closest value the device supports.
+    pub fn clamped_settings(&self) -> &[ClampedSetting] {
+        &self.clamped_settings
+    }
+
+    /// Used vs reserved device memory per heap.
+    pub fn memory_stats(&self) -> Vec<HeapStats> {
+        self.allocator.heap_stats()
+    }
+
+    fn save_screenshot(&self, image_index: usize) -> SetupResult<()> {
+        if !self.render_target.supports_readback() {
+            println!("Screenshots are not supported by this swapchain!");
+            return Ok(());
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let path = PathBuf::from(format!("screenshot_{}.png", timestamp));
+        setup::readback::save_png(&self.read_back_frame(image_index)?, &path)?;
+        println!("Saved screenshot to {}", path.display());
+        Ok(())
+    }
+
+    /// Offscreen images are owned per frame in flight, so the frame index doubles as image index.
+    fn draw_frame_headless(&mut self, current_frame: usize, clock: &FrameClock) -> SetupResult<()> {
+        self.frame_sync_data
+            .wait_for_frame(&self.device, &self.graphics_queue, current_frame)?;
+
+        // offscreen images are used in frame slot order
+        let elapsed_seconds = clock.elapsed_seconds();
+        self.record_frame(current_frame, current_frame, elapsed_seconds)?;
+        self.update_uniform_buffer(current_frame as u32, elapsed_seconds)?;
+
+        let command_buffers = [self.command_buffers[current_frame]];
+        let batch = Batch {
+            command_buffers: &command_buffers,
+            ..Batch::default()
+        };
+        self.frame_sync_data
+            .submit(&self.device, &self.graphics_queue, current_frame, &batch)
+    }
+
+    fn swapchain_data(&self) -> SetupResult<&SwapchainData> {
+        self.render_target
+            .swapchain_data()
+            .ok_or_else(|| SetupError::unsupported("Render target is not a swapchain!"))
+    }
+
+    fn draw_frame(
+        &mut self,
+        current_frame: usize,
+        physical_window_size: &winit::dpi::PhysicalSize<u32>,
+        framebuffer_resized: &mut bool,
+        screenshot_requested: &mut bool,
+        clock: &FrameClock,
+    ) -> SetupResult<()> {
+        let timeout = u64::MAX;
+        self.frame_sync_data
+            .wait_for_frame(&self.device, &self.graphics_queue, current_frame)?;
+
+        let swapchain_data = self.swapchain_data()?;
+        let acquire_next_image_result = unsafe {
+            swapchain_data.swapchain.acquire_next_image(
+                swapchain_data.swapchain_khr,
+                timeout,
+                self.frame_sync_data.image_available_semaphores[current_frame],
+                vk::Fence::null(),
+            )
+        };
+        let (image_index, _is_suboptimal) = match acquire_next_image_result {
+            Ok((image_index, is_suboptimal)) => (image_index, is_suboptimal),
+            Err(result) => match result {
+                vk::Result::ERROR_OUT_OF_DATE_KHR => {
+                    unsafe {
+                        self.recreate_swapchain(physical_window_size)?;
+                    }
+                    return Ok(());
+                }
+                result => {
+                    return Err(SetupError::Vulkan {
+                        context: "Failed to acquire swapchain image",
+                        result,
+                    })
+                }
+            },
+        };
+
+        // the command buffer and uniform buffer of this image may belong to another frame slot
+        self.frame_sync_data.acquire_image(
+            &self.device,
+            &self.graphics_queue,
+            image_index as usize,
+            current_frame,
+        )?;
+
+        let elapsed_seconds = clock.elapsed_seconds();
+        self.record_frame(current_frame, image_index as usize, elapsed_seconds)?;
+        self.update_uniform_buffer(image_index, elapsed_seconds)?;
+
+        let wait_semaphores = [self.frame_sync_data.image_available_semaphores[current_frame]];
+        let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+        let command_buffers = [self.command_buffers[current_frame]];
+        let signal_semaphores = [self.frame_sync_data.render_finished_semaphores[current_frame]];
+
+        let batch = Batch {
+            wait_semaphores: &wait_semaphores,
+            wait_stages: &wait_stages,
+            command_buffers: &command_buffers,
+            signal_semaphores: &signal_semaphores,
+        };
+        self.frame_sync_data
+            .submit(&self.device, &self.graphics_queue, current_frame, &batch)?;
+
+        // the image must be read before it is handed over to the presentation engine
+        if *screenshot_requested {
+            *screenshot_requested = false;
+            self.frame_sync_data.wait_for_frame(
+                &self.device,
+                &self.graphics_queue,
+                current_frame,
+            )?;
+            self.save_screenshot(image_index as usize)?;
+        }
+
+        let swapchain_data = self.swapchain_data()?;
+        let present_queue = self
+            .present_queue
+            .ok_or_else(|| SetupError::unsupported("No present queue available!"))?;
+        let swapchains = [swapchain_data.swapchain_khr];
+        let image_indices = [image_index];
+
+        let present_info = vk::PresentInfoKHR::builder()
+            .wait_semaphores(&signal_semaphores)
+            .swapchains(&swapchains)
+            .image_indices(&image_indices)
+            .build();
+
+        let queue_present_result = unsafe {
+            swapchain_data
+                .swapchain
+                .queue_present(present_queue, &present_info)
+        };
+        let should_recreate_swapchain = match queue_present_result {
+            Ok(is_suboptimal) => is_suboptimal,
+            Err(result) => match result {
+                vk::Result::ERROR_OUT_OF_DATE_KHR => true,
+                result => {
+                    return Err(SetupError::Vulkan {
+                        context: "Failed to present swapchain image",
+                        result,
+                    })
+                }
+            },
+        };
+        if should_recreate_swapchain || *framebuffer_resized {
+            *framebuffer_resized = false;
+            unsafe {
+                self.recreate_swapchain(physical_window_size)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds this frame's draw list and records it into the frame slot's command buffer, once
+    /// the slot's fence has signalled; with recording threads the draws go into secondary command
+    /// buffers that the primary executes.
+    fn record_frame(
+        &mut self,
+        current_frame: usize,
+        image_index: usize,
+        elapsed_seconds: f32,
+    ) -> SetupResult<()> {
+        self.draw_list.reset();
+        let frame_info = FrameInfo {
+            frame_index: current_frame,
+            elapsed_seconds,
+            sub_meshes: &self.mesh.sub_meshes,
+            last_recording_time: self.last_recording_time,
+        };
+        match self.draw_callback.as_mut() {
+            Some(draw_callback) => draw_callback(&mut self.draw_list, &frame_info),
+            None => {
+                let pipeline = if self.wireframe {
+                    PipelineKind::Wireframe
+                } else {
+                    PipelineKind::Opaque
+                };
+                self.draw_list
+                    .draw_all(pipeline, self.mesh.sub_meshes.len());
+            }
+        }
+
+        self.draw_list.validate(self.mesh.sub_meshes.len())?;
+
+        let recording_start = Instant::now();
+        unsafe {
+            self.device
+                .reset_command_pool(
+                    self.frame_command_pools[current_frame],
+                    vk::CommandPoolResetFlags::empty(),
+                )
+                .context("Failed to reset command pool")?;
+        }
+        let record_info = RecordInfo {
+            render_pass: self.render_pass,
+            framebuffer: self.framebuffers[image_index],
+            extent: self.render_target.image_extent(),
+            pipelines: self.pipelines.clone(),
+            pipeline_layout: self.pipeline_layout,
+            material_descriptor_sets: self.descriptor_sets[image_index].clone(),
+            vertex_buffer: self.vertex_buffer.buffer,
+            index_buffer: self.index_buffer.buffer,
+            push_object_transforms: self.push_object_transforms,
+        };
+        match &self.command_workers {
+            Some(command_workers) => {
+                let secondary_command_buffers = command_workers.record(
+                    current_frame,
+                    record_info.clone(),
+                    &self.draw_list.draws,
+                )?;
+                setup::command_buffers::record_with_secondaries(
+                    &self.device,
+                    self.command_buffers[current_frame],
+                    &record_info,
+                    self.draw_list.clear_color,
+                    &secondary_command_buffers,
+                )?;
+            }
+            None => setup::command_buffers::record(
+                &self.device,
+                self.command_buffers[current_frame],
+                &record_info,
+                &self.mesh.sub_meshes,
+                &self.draw_list,
+            )?,
+        }
+        self.last_recording_time = recording_start.elapsed();
+        Ok(())
+    }
+
+    fn update_uniform_buffer(&self, image_index: u32, elapsed_seconds: f32) -> SetupResult<()> {
+        let ubo = UBO {
+            model: Matrix4::from_angle_z(Deg(30.0 * elapsed_seconds)),
+            view: Matrix4::look_at(
+                Point3::new(2.0, 2.0, 1.0),
+                Point3::new(0.0, 0.0, 0.0),
+                Vector3::new(0.0, 0.0, 1.0),
+            ),
+            projection: self.build_projection_matrix(45.0, 1.0, 10.0),
+        };
+
+        self.uniform_buffers[image_index as usize].write(&[ubo])
+    }
+
+    #[rustfmt::skip]
+    fn build_projection_matrix(&self, fov: f32, f_near: f32, f_far: f32) -> Matrix4<f32> {
+        let vk::Extent2D { width, height } = self.render_target.image_extent();
+        let aspect_ratio = width as f32 / height as f32;
+
+        let scale = 1.0 / (0.5 * fov * std::f32::consts::PI / 180.0).tan();
+        let c0r0 = scale / aspect_ratio;
+        let c2r2 = -f_far / (f_far - f_near);
+        let c3r2 = -(f_far * f_near) / (f_far - f_near);
+
+        // perspective projection borrowed from:
+        // https://www.scratchapixel.com/lessons/3d-basic-rendering/perspective-and-orthographic-projection-matrix/building-basic-perspective-projection-matrix
+        Matrix4::new(
+            c0r0,    0.0,    0.0,      0.0,
+             0.0, -scale,    0.0,      0.0,
+             0.0,    0.0,   c2r2,     c3r2,
+             0.0,    0.0,   -1.0,      0.0
+        )
+    }
+
+    fn device_wait_idle(&self) -> SetupResult<()> {
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .context("Failed to wait for device to become idle")
+        }
+    }
+
+    unsafe fn recreate_swapchain(
+        &mut self,
+        physical_window_size: &winit::dpi::PhysicalSize<u32>,
+    ) -> SetupResult<()> {
+        self.device_wait_idle()?;
+        self.drop_framebuffers();
+
+        let instance = &self.context.instance;
+        let physical_device = self.context.physical_device;
+        // offscreen images do not depend on the window and are kept as they are
+        if let Some((surface, surface_khr)) = self.context.surface_pair() {
+            // handing the old swapchain over lets the presentation engine reuse its resources
+            let old_swapchain_khr = self
+                .render_target
+                .swapchain_data()
+                .map_or(vk::SwapchainKHR::null(), |swapchain_data| {
+                    swapchain_data.swapchain_khr
+                });
+            let swapchain_data = SwapchainData::new(
+                instance,
+                physical_device,
+                &self.device,
+                surface,
+                surface_khr,
+                *physical_window_size,
+                &config::present_mode_preferences(&self.present_modes, self.vsync),
+                old_swapchain_khr,
+            )?;
+            let old_render_target = mem::replace(
+                &mut self.render_target,
+                RenderTarget::Swapchain(swapchain_data),
+            );
+            if let RenderTarget::Swapchain(old_swapchain_data) = old_render_target {
+                old_swapchain_data.destroy(&self.device);
+            }
+            self.frame_sync_data
+                .reset_images(self.render_target.images().len());
+        }
+
+        // a resize keeps the format, so only an MSAA change or a new surface format lands here
+        let render_pass_compatibility = (self.render_target.image_format(), self.msaa_samples);
+        if render_pass_compatibility != self.render_pass_compatibility {
+            self.drop_pipelines();
+            let instance = &self.context.instance;
+            self.render_pass = setup::render_pass::create(
+                instance,
+                &self.device,
+                &physical_device,
+                &self.render_target,
+                self.msaa_samples,
+            )?;
+            self.pipelines = setup::graphics_pipeline::create(
+                &self.device,
+                self.render_pass,
+                self.pipeline_layout,
+                self.pipeline_cache.cache,
+                self.msaa_samples,
+                &instance.get_physical_device_features(physical_device),
+                &self.vertex_shader,
+                &self.fragment_shader,
+            )?;
+            self.render_pass_compatibility = render_pass_compatibility;
+        }
+
+        let instance = &self.context.instance;
+        self.color_image = setup::image::create_color_resources(
+            &self.allocator,
+            self.render_target.image_extent(),
+            self.render_target.image_format(),
+            self.msaa_samples,
+        )?;
+        self.depth_image = setup::image::create_depth_resources(
+            instance,
+            &self.allocator,
+            &physical_device,
+            self.render_target.image_extent(),
+            self.msaa_samples,
+        )?;
+
+        self.framebuffers = setup::framebuffers::create(
+            &self.device,
+            &self.render_target,
+            self.render_pass,
+            self.color_image
+                .as_ref()
+                .map(|color_image| &color_image.view),
+            &self.depth_image.view,
+        )?;
+
+        // per-image uniform buffers and descriptor sets only change with the image count
+        if self.uniform_buffers.len() != self.render_target.images().len() {
+            // destroying the pool frees its sets; the null handle keeps Drop from destroying it
+            // again should one of the creations below fail
+            let descriptor_pool =
+                mem::replace(&mut self.descriptor_pool, vk::DescriptorPool::null());
+            self.descriptor_sets.clear();
+            self.device.destroy_descriptor_pool(descriptor_pool, None);
+            self.uniform_buffers =
+                setup::uniform_buffers::create(&self.allocator, &self.render_target.images())?;
+            self.descriptor_pool = setup::uniform_buffers::create_descriptor_pool(
+                &self.device,
+                &self.descriptor_bindings,
+                &self.render_target.images(),
+                self.materials.len(),
+            )?;
+            self.descriptor_sets = setup::uniform_buffers::create_descriptor_sets(
+                &self.device,
+                self.descriptor_pool,
+                self.descriptor_set_layout,
+                &self.descriptor_bindings,
+                &self.uniform_buffers,
+                &self.render_target.images(),
+                &self.materials,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// `None` when no stage has a source or the sources cannot be watched; the latter is
+    /// reported, but the viewer keeps running without hot reload.
+    #[cfg(feature = "shader-compiler")]
+    fn watch_shader_sources(&self) -> Option<(ShaderCompiler, ShaderWatcher)> {
+        let sources = [
+            self.assets.vertex_shader_source_path(),
+            self.assets.fragment_shader_source_path(),
+        ]
+        .iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<PathBuf>>();
+        if sources.is_empty() {
+            return None;
+        }
+        let shader_reload = ShaderCompiler::new()
+            .and_then(|compiler| Ok((compiler, ShaderWatcher::new(&sources)?)));
+        match shader_reload {
+            Ok(shader_reload) => Some(shader_reload),
+            Err(error) => {
+                eprintln!("Warning: {}", error);
+                None
+            }
+        }
+    }
+
+    /// Recompiles the changed sources and swaps in pipelines built from them. A source that does
+    /// not compile, shaders that need a different pipeline layout and failed pipeline creation
+    /// are reported and leave the current pipelines in use; only errors after the current
+    /// pipelines were released are returned.
+    #[cfg(feature = "shader-compiler")]
+    fn reload_shaders(
+        &mut self,
+        compiler: &mut ShaderCompiler,
+        changed_sources: &[PathBuf],
+    ) -> SetupResult<()> {
+        let reloaded = self
+            .compile_changed_shaders(compiler, changed_sources)
+            .and_then(|(vertex_shader, fragment_shader)| {
+                let pipelines = self.create_reloaded_pipelines(&vertex_shader, &fragment_shader)?;
+                Ok((vertex_shader, fragment_shader, pipelines))
+            });
+        let (vertex_shader, fragment_shader, pipelines) = match reloaded {
+            Ok(reloaded) => reloaded,
+            Err(error) => {
+                eprintln!("{}\nKeeping the previous shaders.", error);
+                return Ok(());
+            }
+        };
+
+        // frames are recorded as they come, so the next one already uses the new pipelines
+        self.device_wait_idle()?;
+        unsafe {
+            self.pipelines
+                .iter()
+                .for_each(|pipeline| self.device.destroy_pipeline(*pipeline, None));
+        }
+        self.pipelines = pipelines;
+        self.vertex_shader = vertex_shader;
+        self.fragment_shader = fragment_shader;
+        println!("Reloaded shaders");
+        Ok(())
+    }
+
+    #[cfg(feature = "shader-compiler")]
+    fn compile_changed_shaders(
+        &self,
+        compiler: &mut ShaderCompiler,
+        changed_sources: &[PathBuf],
+    ) -> SetupResult<(ShaderCode, ShaderCode)> {
+        let mut recompile = |source: Option<PathBuf>, current: &ShaderCode| match source {
+            Some(source) if changed_sources.contains(&source) => compiler
+                .compile(&source)
+                .map(|words| ShaderCode::Compiled { source, words }),
+            _ => Ok(current.clone()),
+        };
+        let vertex_shader =
+            recompile(self.assets.vertex_shader_source_path(), &self.vertex_shader)?;
+        let fragment_shader = recompile(
+            self.assets.fragment_shader_source_path(),
+            &self.fragment_shader,
+        )?;
+        Ok((vertex_shader, fragment_shader))
+    }
+
+    /// The descriptor sets and pipeline layout stay as they are, so the new shaders have to
+    /// declare the same bindings and push constants as the current ones.
+    #[cfg(feature = "shader-compiler")]
+    fn create_reloaded_pipelines(
+        &self,
+        vertex_shader: &ShaderCode,
+        fragment_shader: &ShaderCode,
+    ) -> SetupResult<Vec<vk::Pipeline>> {
+        let current_interfaces =
+            setup::graphics_pipeline::reflect_shaders(&self.vertex_shader, &self.fragment_shader)?;
+        let interfaces = setup::graphics_pipeline::reflect_shaders(vertex_shader, fragment_shader)?;
+        if !setup::shader_reflection::same_pipeline_layout(&current_interfaces, &interfaces)? {
+            return Err(SetupError::unsupported(
+                "Shaders changed their descriptor bindings or push constants, which needs a restart!",
+            ));
+        }
+
+        let instance = &self.context.instance;
+        setup::graphics_pipeline::create(
+            &self.device,
+            self.render_pass,
+            self.pipeline_layout,
+            self.pipeline_cache.cache,
+            self.msaa_samples,
+            &unsafe { instance.get_physical_device_features(self.context.physical_device) },
+            vertex_shader,
+            fragment_shader,
+        )
+    }
+
+    /// Destroys the size-dependent handles that are not owned by a resource wrapper; the
+    /// attachments are released when they are replaced or dropped. The handles are taken out as
+    /// they are destroyed, so a recreation failing halfway leaves nothing to destroy twice;
+    /// destroying a null handle is a no-op.
+    unsafe fn drop_framebuffers(&mut self) {
+        mem::take(&mut self.framebuffers)
+            .into_iter()
+            .for_each(|framebuffer| self.device.destroy_framebuffer(framebuffer, None));
+    }
+
+    unsafe fn drop_pipelines(&mut self) {
+        mem::take(&mut self.pipelines)
+            .into_iter()
+            .for_each(|pipeline| self.device.destroy_pipeline(pipeline, None));
+        let render_pass = mem::replace(&mut self.render_pass, vk::RenderPass::null());
+        self.device.destroy_render_pass(render_pass, None);
+    }
+}
+
+impl Drop for VulkanApp {
+    fn drop(&mut self) {
+        unsafe {
+            // resource wrappers free their memory as soon as the fields are dropped
+            let _ = self.device.device_wait_idle();
+            if let Err(error) = self.pipeline_cache.save() {
+                eprintln!("Warning: {}", error);
+            }
+            self.drop_framebuffers();
+            self.drop_pipelines();
+            if let RenderTarget::Swapchain(swapchain_data) = &self.render_target {
+                swapchain_data.destroy(&self.device);
+            }
+
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            self.frame_sync_data
+                .image_available_semaphores
+                .iter()
+                .for_each(|semaphore| self.device.destroy_semaphore(*semaphore, None));
+            self.frame_sync_data
+                .render_finished_semaphores
+                .iter()
+                .for_each(|semaphore| self.device.destroy_semaphore(*semaphore, None));
+            self.frame_sync_data
+                .in_flight_fences
+                .iter()
+                .for_each(|fence| self.device.destroy_fence(*fence, None));
+
+            // destroying the pools frees their command buffers
+            self.frame_command_pools
+                .iter()
+                .for_each(|command_pool| self.device.destroy_command_pool(*command_pool, None));
+            self.device.destroy_command_pool(self.command_pool, None);
+        };
+    }
+}
+
+/// Compiles the configured sources; a stage without a source, or whose source does not compile,
+/// uses its `.spv` file, the last good build.
+#[cfg(feature = "shader-compiler")]
+fn load_shaders(assets: &AssetConfig) -> (ShaderCode, ShaderCode) {
+    let mut compiler = ShaderCompiler::new()
+        .map_err(|error| eprintln!("Warning: {}", error))
+        .ok();
+    let mut load = |source: Option<PathBuf>, spirv: PathBuf| match (compiler.as_mut(), source) {
+        (Some(compiler), Some(source)) => match compiler.compile(&source) {
+            Ok(words) => ShaderCode::Compiled { source, words },
+            Err(error) => {
+                eprintln!("Warning: {}\nUsing {} instead.", error, spirv.display());
+                ShaderCode::File(spirv)
+            }
+        },
+        _ => ShaderCode::File(spirv),
+    };
+    let vertex_shader = load(
+        assets.vertex_shader_source_path(),
+        assets.vertex_shader_path(),
+    );
+    let fragment_shader = load(
+        assets.fragment_shader_source_path(),
+        assets.fragment_shader_path(),
+    );
+    (vertex_shader, fragment_shader)
+}
+
+#[cfg(not(feature = "shader-compiler"))]
+fn load_shaders(assets: &AssetConfig) -> (ShaderCode, ShaderCode) {
+    (
+        ShaderCode::File(assets.vertex_shader_path()),
+        ShaderCode::File(assets.fragment_shader_path()),
+    )
+}
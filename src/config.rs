@@ -0,0 +1,727 @@
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use ash::vk;
+use serde::Deserialize;
+
+use crate::setup::error::{SetupError, SetupResult};
+
+/// Read from the working directory when no `--config` is given; it is optional there.
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+pub const DEFAULT_PIPELINE_CACHE_FILE: &str = "pipeline_cache.bin";
+const DEFAULT_MODEL: &str = "src/resources/models/viking_room.obj";
+
+/// Model, texture and shader locations. Relative paths are looked up in `root`, see
+/// `default_asset_root` for where that is unless configured.
+#[derive(Clone, Debug)]
+pub struct AssetConfig {
+    pub root: PathBuf,
+    pub model: PathBuf,
+    /// Diffuse texture for materials that do not have one.
+    pub texture: Option<PathBuf>,
+    pub vertex_shader: PathBuf,
+    pub fragment_shader: PathBuf,
+    /// GLSL or HLSL sources compiled at startup and on change when the crate is built with the
+    /// `shader-compiler` feature; they take precedence over the `.spv` files, which remain the
+    /// fallback while a source does not compile.
+    pub vertex_shader_source: Option<PathBuf>,
+    pub fragment_shader_source: Option<PathBuf>,
+}
+
+impl Default for AssetConfig {
+    fn default() -> Self {
+        Self {
+            root: default_asset_root(),
+            model: PathBuf::from(DEFAULT_MODEL),
+            texture: None,
+            vertex_shader: PathBuf::from("src/shaders/vert.spv"),
+            fragment_shader: PathBuf::from("src/shaders/frag.spv"),
+            vertex_shader_source: Some(PathBuf::from("src/shaders/src/shader.vert")),
+            fragment_shader_source: Some(PathBuf::from("src/shaders/src/shader.frag")),
+        }
+    }
+}
+
+/// The executable's directory when the default model is shipped next to it, and the working
+/// directory otherwise, e.g. the crate directory under `cargo run`.
+fn default_asset_root() -> PathBuf {
+    let executable_dir = env::current_exe()
+        .ok()
+        .and_then(|executable| executable.parent().map(Path::to_path_buf));
+    match executable_dir {
+        Some(executable_dir) if executable_dir.join(DEFAULT_MODEL).is_file() => executable_dir,
+        _ => env::current_dir().unwrap_or_default(),
+    }
+}
+
+impl AssetConfig {
+    pub fn model_path(&self) -> PathBuf {
+        self.root.join(&self.model)
+    }
+
+    pub fn texture_path(&self) -> Option<PathBuf> {
+        self.texture.as_ref().map(|texture| self.root.join(texture))
+    }
+
+    pub fn vertex_shader_path(&self) -> PathBuf {
+        self.root.join(&self.vertex_shader)
+    }
+
+    pub fn fragment_shader_path(&self) -> PathBuf {
+        self.root.join(&self.fragment_shader)
+    }
+
+    pub fn vertex_shader_source_path(&self) -> Option<PathBuf> {
+        self.vertex_shader_source
+            .as_ref()
+            .map(|source| self.root.join(source))
+    }
+
+    pub fn fragment_shader_source_path(&self) -> Option<PathBuf> {
+        self.fragment_shader_source
+            .as_ref()
+            .map(|source| self.root.join(source))
+    }
+
+    /// Applies the `[assets]` table of a config file; its `root` is relative to the file.
+    fn apply_file(&mut self, file: AssetFile, config_dir: &Path) {
+        if let Some(root) = file.root {
+            self.root = config_dir.join(root);
+        }
+        if let Some(model) = file.model {
+            self.model = model;
+        }
+        if let Some(texture) = file.texture {
+            self.texture = Some(texture);
+        }
+        if let Some(vertex_shader) = file.vertex_shader {
+            self.vertex_shader = vertex_shader;
+        }
+        if let Some(fragment_shader) = file.fragment_shader {
+            self.fragment_shader = fragment_shader;
+        }
+        // an empty path switches compiling that stage off
+        if let Some(source) = file.vertex_shader_source {
+            self.vertex_shader_source = Some(source).filter(|path| !path.as_os_str().is_empty());
+        }
+        if let Some(source) = file.fragment_shader_source {
+            self.fragment_shader_source = Some(source).filter(|path| !path.as_os_str().is_empty());
+        }
+    }
+
+    /// Applies `--asset-root`, `--model`, `--texture`, `--vertex-shader`, `--fragment-shader`,
+    /// `--vertex-shader-source` and `--fragment-shader-source`; a relative `--asset-root` is taken
+    /// from the working directory.
+    fn apply_args(&mut self, args: &[String]) {
+        if let Some(root) = get_arg_value(args, "--asset-root") {
+            self.root = env::current_dir()
+                .map(|current_dir| current_dir.join(&root))
+                .unwrap_or_else(|_| PathBuf::from(root));
+        }
+        if let Some(model) = get_arg_value(args, "--model") {
+            self.model = PathBuf::from(model);
+        }
+        if let Some(texture) = get_arg_value(args, "--texture") {
+            self.texture = Some(PathBuf::from(texture));
+        }
+        if let Some(vertex_shader) = get_arg_value(args, "--vertex-shader") {
+            self.vertex_shader = PathBuf::from(vertex_shader);
+        }
+        if let Some(fragment_shader) = get_arg_value(args, "--fragment-shader") {
+            self.fragment_shader = PathBuf::from(fragment_shader);
+        }
+        if let Some(source) = get_arg_value(args, "--vertex-shader-source") {
+            self.vertex_shader_source = Some(PathBuf::from(source));
+        }
+        if let Some(source) = get_arg_value(args, "--fragment-shader-source") {
+            self.fragment_shader_source = Some(PathBuf::from(source));
+        }
+    }
+}
+
+/// Window creation settings; headless rendering uses the size as its image extent.
+#[derive(Clone, Debug)]
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+    pub title: String,
+    /// Borderless on the primary monitor.
+    pub fullscreen: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 600,
+            title: String::from("Vulkan tutorial"),
+            fullscreen: false,
+        }
+    }
+}
+
+impl WindowConfig {
+    fn apply_file(&mut self, file: WindowFile) -> SetupResult<()> {
+        if let Some(width) = file.width {
+            self.width = width;
+        }
+        if let Some(height) = file.height {
+            self.height = height;
+        }
+        if let Some(title) = file.title {
+            self.title = title;
+        }
+        if let Some(fullscreen) = file.fullscreen {
+            self.fullscreen = fullscreen;
+        }
+        self.validate()
+    }
+
+    /// Applies `--width`, `--height`, `--title` and `--fullscreen`/`--windowed`.
+    fn apply_args(&mut self, args: &[String]) -> SetupResult<()> {
+        if let Some(width) = parse_arg(args, "--width")? {
+            self.width = width;
+        }
+        if let Some(height) = parse_arg(args, "--height")? {
+            self.height = height;
+        }
+        if let Some(title) = get_arg_value(args, "--title") {
+            self.title = title;
+        }
+        if let Some(fullscreen) = get_flag(args, "--fullscreen", "--windowed") {
+            self.fullscreen = fullscreen;
+        }
+        self.validate()
+    }
+
+    fn validate(&self) -> SetupResult<()> {
+        if self.width == 0 || self.height == 0 {
+            return Err(SetupError::unsupported(format!(
+                "Window size {}x{} must not be empty!",
+                self.width, self.height
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresentMode {
+    Immediate,
+    FifoRelaxed,
+    Mailbox,
+    Fifo,
+}
+
+impl PresentMode {
+    /// Modes that may show a partially presented image; vsync leaves them out.
+    pub fn tears(self) -> bool {
+        match self {
+            PresentMode::Immediate | PresentMode::FifoRelaxed => true,
+            PresentMode::Mailbox | PresentMode::Fifo => false,
+        }
+    }
+
+    pub fn to_vk(self) -> vk::PresentModeKHR {
+        match self {
+            PresentMode::Immediate => vk::PresentModeKHR::IMMEDIATE,
+            PresentMode::FifoRelaxed => vk::PresentModeKHR::FIFO_RELAXED,
+            PresentMode::Mailbox => vk::PresentModeKHR::MAILBOX,
+            PresentMode::Fifo => vk::PresentModeKHR::FIFO,
+        }
+    }
+}
+
+impl FromStr for PresentMode {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "immediate" => Ok(PresentMode::Immediate),
+            "fifo_relaxed" => Ok(PresentMode::FifoRelaxed),
+            "mailbox" => Ok(PresentMode::Mailbox),
+            "fifo" => Ok(PresentMode::Fifo),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Renderer settings. Values the device cannot provide are clamped when the renderer is created
+/// and reported as `ClampedSetting`s rather than failing.
+#[derive(Clone, Debug)]
+pub struct GraphicsConfig {
+    /// Skips the modes in `present_modes` that tear; can be toggled at runtime.
+    pub vsync: bool,
+    /// Present modes in order of preference; the first one the surface supports is used, and FIFO
+    /// if none is.
+    pub present_modes: Vec<PresentMode>,
+    /// Rounded down to a sample count the device supports; 1 disables multisampling.
+    pub msaa_samples: u32,
+    /// Maximum sampler anisotropy; 1 disables anisotropic filtering.
+    pub anisotropy: f32,
+    pub frames_in_flight: usize,
+    /// Worker threads recording secondary command buffers for slices of the draw list; 0 records
+    /// everything inline on the main thread.
+    pub recording_threads: usize,
+    /// Synchronizes frames and uploads with a timeline semaphore per queue where the device
+    /// supports them, instead of fences and idle waits.
+    pub timeline_semaphores: bool,
+    pub validation: bool,
+    /// File compiled pipelines are cached in between runs; `None` keeps the cache in memory only.
+    /// Relative paths are taken from the config file's directory when set there, like the asset
+    /// root, and from the working directory otherwise.
+    pub pipeline_cache: Option<PathBuf>,
+}
+
+impl Default for GraphicsConfig {
+    fn default() -> Self {
+        Self {
+            vsync: true,
+            present_modes: vec![
+                PresentMode::Immediate,
+                PresentMode::FifoRelaxed,
+                PresentMode::Mailbox,
+                PresentMode::Fifo,
+            ],
+            msaa_samples: 4,
+            anisotropy: 16.0,
+            frames_in_flight: 2,
+            recording_threads: 0,
+            timeline_semaphores: true,
+            validation: cfg!(debug_assertions),
+            pipeline_cache: Some(PathBuf::from(DEFAULT_PIPELINE_CACHE_FILE)),
+        }
+    }
+}
+
+impl GraphicsConfig {
+    /// `anisotropy` limited to what the device's samplers support; at least 1.
+    pub fn clamped_anisotropy(&self, max_sampler_anisotropy: f32) -> f32 {
+        self.anisotropy.max(1.0).min(max_sampler_anisotropy)
+    }
+
+    /// `frames_in_flight` limited to the images rendered to; at least 1.
+    pub fn clamped_frames_in_flight(&self, image_count: usize) -> usize {
+        self.frames_in_flight.max(1).min(image_count)
+    }
+
+    fn apply_file(&mut self, file: GraphicsFile, config_dir: &Path) {
+        if let Some(vsync) = file.vsync {
+            self.vsync = vsync;
+        }
+        if let Some(present_modes) = file.present_modes {
+            self.present_modes = present_modes;
+        }
+        if let Some(msaa_samples) = file.msaa_samples {
+            self.msaa_samples = msaa_samples;
+        }
+        if let Some(anisotropy) = file.anisotropy {
+            self.anisotropy = anisotropy;
+        }
+        if let Some(frames_in_flight) = file.frames_in_flight {
+            self.frames_in_flight = frames_in_flight;
+        }
+        if let Some(recording_threads) = file.recording_threads {
+            self.recording_threads = recording_threads;
+        }
+        if let Some(timeline_semaphores) = file.timeline_semaphores {
+            self.timeline_semaphores = timeline_semaphores;
+        }
+        if let Some(validation) = file.validation {
+            self.validation = validation;
+        }
+        // an empty path turns the file off
+        if let Some(pipeline_cache) = file.pipeline_cache {
+            self.pipeline_cache = Some(pipeline_cache)
+                .filter(|path| !path.as_os_str().is_empty())
+                .map(|path| config_dir.join(path));
+        }
+    }
+
+    /// Applies `--vsync`/`--no-vsync`, `--present-modes` (comma separated), `--msaa`, `--anisotropy`,
+    /// `--frames-in-flight`, `--recording-threads`, `--timeline-semaphores`/
+    /// `--no-timeline-semaphores`, `--validation`/`--no-validation` and `--pipeline-cache <path>`/
+    /// `--no-pipeline-cache`.
+    fn apply_args(&mut self, args: &[String]) -> SetupResult<()> {
+        if let Some(vsync) = get_flag(args, "--vsync", "--no-vsync") {
+            self.vsync = vsync;
+        }
+        if let Some(present_modes) = get_arg_value(args, "--present-modes") {
+            self.present_modes = present_modes
+                .split(',')
+                .map(|present_mode| {
+                    present_mode
+                        .trim()
+                        .parse()
+                        .map_err(|_| SetupError::Argument {
+                            name: String::from("--present-modes"),
+                            value: present_modes.clone(),
+                        })
+                })
+                .collect::<SetupResult<Vec<PresentMode>>>()?;
+        }
+        if let Some(msaa_samples) = parse_arg(args, "--msaa")? {
+            self.msaa_samples = msaa_samples;
+        }
+        if let Some(anisotropy) = parse_arg(args, "--anisotropy")? {
+            self.anisotropy = anisotropy;
+        }
+        if let Some(frames_in_flight) = parse_arg(args, "--frames-in-flight")? {
+            self.frames_in_flight = frames_in_flight;
+        }
+        if let Some(recording_threads) = parse_arg(args, "--recording-threads")? {
+            self.recording_threads = recording_threads;
+        }
+        if let Some(timeline_semaphores) =
+            get_flag(args, "--timeline-semaphores", "--no-timeline-semaphores")
+        {
+            self.timeline_semaphores = timeline_semaphores;
+        }
+        if let Some(validation) = get_flag(args, "--validation", "--no-validation") {
+            self.validation = validation;
+        }
+        if let Some(pipeline_cache) = get_arg_value(args, "--pipeline-cache") {
+            self.pipeline_cache = Some(PathBuf::from(pipeline_cache));
+        }
+        if args.iter().any(|arg| arg == "--no-pipeline-cache") {
+            self.pipeline_cache = None;
+        }
+        Ok(())
+    }
+}
+
+/// The Vulkan present modes to try in order: `present_modes` without the tearing ones when
+/// `vsync` is on.
+pub fn present_mode_preferences(
+    present_modes: &[PresentMode],
+    vsync: bool,
+) -> Vec<vk::PresentModeKHR> {
+    present_modes
+        .iter()
+        .filter(|present_mode| !(vsync && present_mode.tears()))
+        .map(|present_mode| present_mode.to_vk())
+        .collect()
+}
+
+/// A setting that was replaced with the closest value the device or driver supports.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClampedSetting {
+    pub name: &'static str,
+    pub requested: String,
+    pub applied: String,
+}
+
+impl ClampedSetting {
+    pub fn new<R: fmt::Debug, A: fmt::Debug>(name: &'static str, requested: R, applied: A) -> Self {
+        Self {
+            name,
+            requested: format!("{:?}", requested),
+            applied: format!("{:?}", applied),
+        }
+    }
+}
+
+impl fmt::Display for ClampedSetting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} is not supported, using {}",
+            self.name, self.requested, self.applied
+        )
+    }
+}
+
+/// Everything the binary reads from its config file and command line.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    pub assets: AssetConfig,
+    pub window: WindowConfig,
+    pub graphics: GraphicsConfig,
+}
+
+impl Config {
+    /// Starts from the defaults, applies the config file named by `--config` (or
+    /// `DEFAULT_CONFIG_FILE` if present) and then the command-line overrides.
+    pub fn load(args: &[String]) -> SetupResult<Self> {
+        let mut config = Config::default();
+
+        let config_path = match get_arg_value(args, "--config") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|path| path.is_file()),
+        };
+        if let Some(config_path) = config_path {
+            let file = read_config_file(&config_path)?;
+            let config_dir = config_path.parent().unwrap_or_else(|| Path::new(""));
+            if let Some(assets) = file.assets {
+                config.assets.apply_file(assets, config_dir);
+            }
+            if let Some(window) = file.window {
+                config.window.apply_file(window)?;
+            }
+            if let Some(graphics) = file.graphics {
+                config.graphics.apply_file(graphics, config_dir);
+            }
+        }
+
+        config.assets.apply_args(args);
+        config.window.apply_args(args)?;
+        config.graphics.apply_args(args)?;
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    assets: Option<AssetFile>,
+    window: Option<WindowFile>,
+    graphics: Option<GraphicsFile>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AssetFile {
+    root: Option<PathBuf>,
+    model: Option<PathBuf>,
+    texture: Option<PathBuf>,
+    vertex_shader: Option<PathBuf>,
+    fragment_shader: Option<PathBuf>,
+    vertex_shader_source: Option<PathBuf>,
+    fragment_shader_source: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WindowFile {
+    width: Option<u32>,
+    height: Option<u32>,
+    title: Option<String>,
+    fullscreen: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GraphicsFile {
+    vsync: Option<bool>,
+    present_modes: Option<Vec<PresentMode>>,
+    msaa_samples: Option<u32>,
+    anisotropy: Option<f32>,
+    frames_in_flight: Option<usize>,
+    recording_threads: Option<usize>,
+    timeline_semaphores: Option<bool>,
+    validation: Option<bool>,
+    pipeline_cache: Option<PathBuf>,
+}
+
+fn read_config_file(path: &Path) -> SetupResult<ConfigFile> {
+    let source = fs::read_to_string(path).map_err(|source| SetupError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    toml::from_str(&source).map_err(|source| SetupError::Config {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+pub fn get_arg_value(args: &[String], name: &str) -> Option<String> {
+    args.iter()
+        .skip_while(|arg| arg.as_str() != name)
+        .nth(1)
+        .cloned()
+}
+
+/// `Some(true)` for `on`, `Some(false)` for `off`; the later of the two wins.
+fn get_flag(args: &[String], on: &str, off: &str) -> Option<bool> {
+    args.iter().rev().find_map(|arg| {
+        if arg == on {
+            Some(true)
+        } else if arg == off {
+            Some(false)
+        } else {
+            None
+        }
+    })
+}
+
+/// `None` when the argument is missing, an error when its value does not parse.
+pub fn parse_arg<T: FromStr>(args: &[String], name: &str) -> SetupResult<Option<T>> {
+    get_arg_value(args, name)
+        .map(|value| {
+            value.parse().map_err(|_| SetupError::Argument {
+                name: name.to_string(),
+                value,
+            })
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    /// Writes `source` to a config file in a fresh directory and loads it with `extra_args`.
+    fn load_file(name: &str, source: &str, extra_args: &[&str]) -> SetupResult<(Config, PathBuf)> {
+        let dir = env::temp_dir().join(format!("config-test-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(&path, source).unwrap();
+
+        let mut all_args = args(&["--config", path.to_str().unwrap()]);
+        all_args.extend(args(extra_args));
+        let config = Config::load(&all_args);
+        fs::remove_dir_all(&dir).unwrap();
+        config.map(|config| (config, dir))
+    }
+
+    #[test]
+    fn file_settings_override_the_defaults() {
+        let (config, _) = load_file(
+            "override",
+            r#"
+                [window]
+                width = 1024
+                title = "test"
+
+                [graphics]
+                vsync = false
+                present_modes = ["mailbox", "fifo"]
+                msaa_samples = 8
+                recording_threads = 3
+            "#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(config.window.width, 1024);
+        assert_eq!(config.window.height, 600);
+        assert_eq!(config.window.title, "test");
+        assert!(!config.graphics.vsync);
+        assert_eq!(
+            config.graphics.present_modes,
+            vec![PresentMode::Mailbox, PresentMode::Fifo]
+        );
+        assert_eq!(config.graphics.msaa_samples, 8);
+        assert_eq!(config.graphics.recording_threads, 3);
+        assert_eq!(config.graphics.frames_in_flight, 2);
+    }
+
+    #[test]
+    fn arguments_override_the_file() {
+        let (config, _) = load_file(
+            "arguments",
+            "[graphics]\nmsaa_samples = 8\nvsync = false\n",
+            &["--msaa", "2", "--vsync", "--present-modes", "fifo, mailbox"],
+        )
+        .unwrap();
+
+        assert_eq!(config.graphics.msaa_samples, 2);
+        assert!(config.graphics.vsync);
+        assert_eq!(
+            config.graphics.present_modes,
+            vec![PresentMode::Fifo, PresentMode::Mailbox]
+        );
+    }
+
+    #[test]
+    fn relative_paths_in_the_file_resolve_against_it() {
+        let (config, dir) = load_file(
+            "paths",
+            "[assets]\nroot = \"assets\"\nmodel = \"room.obj\"\n[graphics]\npipeline_cache = \"cache.bin\"\n",
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.assets.model_path(),
+            dir.join("assets").join("room.obj")
+        );
+        assert_eq!(config.graphics.pipeline_cache, Some(dir.join("cache.bin")));
+    }
+
+    #[test]
+    fn empty_paths_in_the_file_switch_features_off() {
+        let (config, _) = load_file(
+            "empty",
+            "[assets]\nvertex_shader_source = \"\"\n[graphics]\npipeline_cache = \"\"\n",
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(config.assets.vertex_shader_source, None);
+        assert!(config.assets.fragment_shader_source.is_some());
+        assert_eq!(config.graphics.pipeline_cache, None);
+    }
+
+    #[test]
+    fn invalid_files_and_arguments_are_rejected() {
+        let unknown_key = load_file("unknown", "[graphics]\nmsaa = 4\n", &[]);
+        assert!(matches!(unknown_key, Err(SetupError::Config { .. })));
+
+        let empty_window = load_file("window", "[window]\nwidth = 0\n", &[]);
+        assert!(matches!(empty_window, Err(SetupError::Unsupported(_))));
+
+        let present_mode = load_file("mode", "", &["--present-modes", "fifo,vsync"]);
+        assert!(matches!(
+            present_mode,
+            Err(SetupError::Argument { ref name, .. }) if name == "--present-modes"
+        ));
+
+        let msaa = load_file("msaa", "", &["--msaa", "four"]);
+        assert!(matches!(
+            msaa,
+            Err(SetupError::Argument { ref value, .. }) if value == "four"
+        ));
+    }
+
+    #[test]
+    fn later_flags_win() {
+        let flag_args = args(&["--no-vsync", "--vsync", "--validation", "--no-validation"]);
+        assert_eq!(get_flag(&flag_args, "--vsync", "--no-vsync"), Some(true));
+        assert_eq!(
+            get_flag(&flag_args, "--validation", "--no-validation"),
+            Some(false)
+        );
+        assert_eq!(get_flag(&flag_args, "--fullscreen", "--windowed"), None);
+    }
+
+    #[test]
+    fn vsync_leaves_out_tearing_present_modes() {
+        let present_modes = GraphicsConfig::default().present_modes;
+        assert_eq!(
+            present_mode_preferences(&present_modes, true),
+            vec![vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::FIFO]
+        );
+        assert_eq!(present_mode_preferences(&present_modes, false).len(), 4);
+    }
+
+    #[test]
+    fn anisotropy_is_clamped_to_the_device_range() {
+        let graphics = |anisotropy| GraphicsConfig {
+            anisotropy,
+            ..GraphicsConfig::default()
+        };
+        assert_eq!(graphics(16.0).clamped_anisotropy(8.0), 8.0);
+        assert_eq!(graphics(4.0).clamped_anisotropy(16.0), 4.0);
+        assert_eq!(graphics(0.0).clamped_anisotropy(16.0), 1.0);
+    }
+
+    #[test]
+    fn frames_in_flight_are_clamped_to_the_image_count() {
+        let graphics = |frames_in_flight| GraphicsConfig {
+            frames_in_flight,
+            ..GraphicsConfig::default()
+        };
+        assert_eq!(graphics(4).clamped_frames_in_flight(3), 3);
+        assert_eq!(graphics(2).clamped_frames_in_flight(3), 2);
+        assert_eq!(graphics(0).clamped_frames_in_flight(3), 1);
+    }
+}
@@ -0,0 +1,11 @@
+extern crate ash;
+extern crate cgmath;
+extern crate field_offset;
+#[cfg(target_os = "windows")]
+extern crate winapi;
+extern crate winit;
+
+pub mod app;
+pub mod config;
+pub mod setup;
+pub mod structs;
@@ -0,0 +1,535 @@
+use std::{
+    ptr::NonNull,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use ash::{
+    version::{DeviceV1_0, InstanceV1_0},
+    vk, Device, Instance,
+};
+
+use crate::setup::buffer;
+use crate::setup::error::{SetupError, SetupResult, VkResultExt};
+
+/// Size of the `vkAllocateMemory` blocks sub-allocations are carved from on large heaps.
+const DEFAULT_BLOCK_SIZE: vk::DeviceSize = 64 * 1024 * 1024;
+/// Heaps up to this size get blocks of an eighth of the heap instead, so they are not exhausted
+/// by a couple of mostly empty blocks.
+const SMALL_HEAP_MAX_SIZE: vk::DeviceSize = 1024 * 1024 * 1024;
+
+/// How a resource lays out its memory. Buffers and linear images must not share a
+/// bufferImageGranularity-sized page with optimal-tiling images.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResourceKind {
+    Linear,
+    Optimal,
+}
+
+/// Used vs reserved bytes of one memory heap.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeapStats {
+    pub heap_index: u32,
+    pub heap_size: vk::DeviceSize,
+    /// Bytes handed out to live sub-allocations.
+    pub used_bytes: vk::DeviceSize,
+    /// Bytes held in `vkAllocateMemory` blocks, used or not.
+    pub reserved_bytes: vk::DeviceSize,
+    pub block_count: usize,
+    pub allocation_count: usize,
+}
+
+/// Host address of a persistently mapped block.
+#[derive(Clone, Copy)]
+struct MappedPtr(NonNull<u8>);
+
+// the mapping lives as long as its block and is only written through `&self` helpers of the
+// owning resource; ordering those writes against the GPU is up to the caller, as with any mapping
+unsafe impl Send for MappedPtr {}
+unsafe impl Sync for MappedPtr {}
+
+/// A sub-range of a memory block; released with `Allocator::free`.
+pub struct Allocation {
+    pub memory: vk::DeviceMemory,
+    pub offset: vk::DeviceSize,
+    pub size: vk::DeviceSize,
+    mapped_ptr: Option<MappedPtr>,
+    block_id: u64,
+}
+
+impl Allocation {
+    /// Host address of the allocation if it lives in host-visible memory.
+    pub fn mapped_ptr(&self) -> Option<*mut u8> {
+        self.mapped_ptr.map(|ptr| ptr.0.as_ptr())
+    }
+}
+
+#[derive(Clone, Copy)]
+struct FreeRange {
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+}
+
+struct MemoryBlock {
+    id: u64,
+    memory: vk::DeviceMemory,
+    size: vk::DeviceSize,
+    memory_type_index: u32,
+    kind: Option<ResourceKind>,
+    mapped_ptr: Option<MappedPtr>,
+    /// Sorted by offset and never adjacent to each other.
+    free_ranges: Vec<FreeRange>,
+    used_bytes: vk::DeviceSize,
+    allocation_count: usize,
+}
+
+impl MemoryBlock {
+    /// First-fit search; the alignment padding in front of the allocation stays free.
+    fn allocate(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> Option<vk::DeviceSize> {
+        let (index, offset) = self
+            .free_ranges
+            .iter()
+            .enumerate()
+            .find_map(|(index, range)| {
+                let offset = align_up(range.offset, alignment);
+                if offset + size <= range.offset + range.size {
+                    Some((index, offset))
+                } else {
+                    None
+                }
+            })?;
+
+        let range = self.free_ranges.remove(index);
+        let tail = FreeRange {
+            offset: offset + size,
+            size: range.offset + range.size - (offset + size),
+        };
+        if tail.size > 0 {
+            self.free_ranges.insert(index, tail);
+        }
+        if offset > range.offset {
+            self.free_ranges.insert(
+                index,
+                FreeRange {
+                    offset: range.offset,
+                    size: offset - range.offset,
+                },
+            );
+        }
+
+        self.used_bytes += size;
+        self.allocation_count += 1;
+        Some(offset)
+    }
+
+    fn free(&mut self, offset: vk::DeviceSize, size: vk::DeviceSize) {
+        let index = self
+            .free_ranges
+            .iter()
+            .position(|range| range.offset > offset)
+            .unwrap_or(self.free_ranges.len());
+        self.free_ranges.insert(index, FreeRange { offset, size });
+
+        // merge with the following range first so `index` stays valid
+        if index + 1 < self.free_ranges.len() {
+            let next = self.free_ranges[index + 1];
+            if offset + size == next.offset {
+                self.free_ranges[index].size += next.size;
+                self.free_ranges.remove(index + 1);
+            }
+        }
+        if index > 0 {
+            let previous = self.free_ranges[index - 1];
+            if previous.offset + previous.size == offset {
+                self.free_ranges[index - 1].size += self.free_ranges[index].size;
+                self.free_ranges.remove(index);
+            }
+        }
+
+        self.used_bytes -= size;
+        self.allocation_count -= 1;
+    }
+}
+
+struct AllocatorState {
+    blocks: Vec<MemoryBlock>,
+    next_block_id: u64,
+}
+
+/// Sub-allocates resources from large `vkAllocateMemory` blocks, one set of blocks per memory
+/// type. Host-visible blocks stay mapped for their whole lifetime.
+pub struct Allocator {
+    device: Arc<Device>,
+    instance: Instance,
+    physical_device: vk::PhysicalDevice,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    buffer_image_granularity: vk::DeviceSize,
+    state: Mutex<AllocatorState>,
+}
+
+impl Allocator {
+    pub fn new(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &Arc<Device>,
+    ) -> Self {
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let limits = unsafe { instance.get_physical_device_properties(physical_device) }.limits;
+
+        Allocator {
+            device: Arc::clone(device),
+            instance: instance.clone(),
+            physical_device,
+            memory_properties,
+            buffer_image_granularity: limits.buffer_image_granularity,
+            state: Mutex::new(AllocatorState {
+                blocks: vec![],
+                next_block_id: 0,
+            }),
+        }
+    }
+
+    pub fn device(&self) -> &Arc<Device> {
+        &self.device
+    }
+
+    pub fn allocate(
+        &self,
+        requirements: vk::MemoryRequirements,
+        properties: vk::MemoryPropertyFlags,
+        kind: ResourceKind,
+    ) -> SetupResult<Allocation> {
+        let memory_type_index = buffer::find_memory_type_index(
+            &self.instance,
+            &self.physical_device,
+            requirements.memory_type_bits,
+            properties,
+        )?;
+        // with a granularity of 1 linear and optimal resources may share pages, hence blocks
+        let kind = if self.buffer_image_granularity > 1 {
+            Some(kind)
+        } else {
+            None
+        };
+
+        let mut state = self.lock_state();
+        let existing = state
+            .blocks
+            .iter_mut()
+            .filter(|block| block.memory_type_index == memory_type_index && block.kind == kind)
+            .find_map(|block| {
+                block
+                    .allocate(requirements.size, requirements.alignment)
+                    .map(|offset| (block.id, block.memory, block.mapped_ptr, offset))
+            });
+        let (block_id, memory, block_mapped_ptr, offset) = match existing {
+            Some(allocation) => allocation,
+            None => {
+                let block_size = self.block_size(memory_type_index).max(requirements.size);
+                let mut block =
+                    self.create_block(&mut state, memory_type_index, kind, block_size)?;
+                let offset = match block.allocate(requirements.size, requirements.alignment) {
+                    Some(offset) => offset,
+                    None => {
+                        self.destroy_block(&block);
+                        return Err(SetupError::unsupported(format!(
+                            "Fresh memory block of {} bytes is too small for {} bytes aligned to {}!",
+                            block_size, requirements.size, requirements.alignment
+                        )));
+                    }
+                };
+                let allocation = (block.id, block.memory, block.mapped_ptr, offset);
+                state.blocks.push(block);
+                allocation
+            }
+        };
+
+        let mapped_ptr = block_mapped_ptr.map(|ptr| unsafe {
+            MappedPtr(NonNull::new_unchecked(ptr.0.as_ptr().add(offset as usize)))
+        });
+        Ok(Allocation {
+            memory,
+            offset,
+            size: requirements.size,
+            mapped_ptr,
+            block_id,
+        })
+    }
+
+    /// Returns the range to its block. One empty block per memory type and resource kind is
+    /// kept around so a resource that is freed and recreated every frame does not hit
+    /// `vkAllocateMemory` each time; further empty blocks are released right away.
+    pub fn free(&self, allocation: &Allocation) -> SetupResult<()> {
+        let mut state = self.lock_state();
+        let index = state
+            .blocks
+            .iter()
+            .position(|block| block.id == allocation.block_id)
+            .ok_or_else(|| {
+                SetupError::unsupported(format!(
+                    "Allocation from block {} does not belong to this allocator!",
+                    allocation.block_id
+                ))
+            })?;
+        let block = &mut state.blocks[index];
+        block.free(allocation.offset, allocation.size);
+        if block.allocation_count > 0 {
+            return Ok(());
+        }
+
+        if has_spare_empty_block(&state.blocks, index) {
+            let block = state.blocks.remove(index);
+            self.destroy_block(&block);
+        }
+        Ok(())
+    }
+
+    pub fn heap_stats(&self) -> Vec<HeapStats> {
+        let mut stats = self.memory_properties.memory_heaps
+            [..self.memory_properties.memory_heap_count as usize]
+            .iter()
+            .enumerate()
+            .map(|(heap_index, heap)| HeapStats {
+                heap_index: heap_index as u32,
+                heap_size: heap.size,
+                ..HeapStats::default()
+            })
+            .collect::<Vec<HeapStats>>();
+
+        for block in self.lock_state().blocks.iter() {
+            let heap_stats = &mut stats[self.heap_index(block.memory_type_index) as usize];
+            heap_stats.used_bytes += block.used_bytes;
+            heap_stats.reserved_bytes += block.size;
+            heap_stats.block_count += 1;
+            heap_stats.allocation_count += block.allocation_count;
+        }
+        stats
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, AllocatorState> {
+        self.state.lock().expect("Allocator state is poisoned!")
+    }
+
+    fn heap_index(&self, memory_type_index: u32) -> u32 {
+        self.memory_properties.memory_types[memory_type_index as usize].heap_index
+    }
+
+    fn block_size(&self, memory_type_index: u32) -> vk::DeviceSize {
+        let heap_size =
+            self.memory_properties.memory_heaps[self.heap_index(memory_type_index) as usize].size;
+        if heap_size <= SMALL_HEAP_MAX_SIZE {
+            heap_size / 8
+        } else {
+            DEFAULT_BLOCK_SIZE
+        }
+    }
+
+    fn create_block(
+        &self,
+        state: &mut AllocatorState,
+        memory_type_index: u32,
+        kind: Option<ResourceKind>,
+        size: vk::DeviceSize,
+    ) -> SetupResult<MemoryBlock> {
+        let allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(size)
+            .memory_type_index(memory_type_index)
+            .build();
+        let memory = unsafe {
+            self.device
+                .allocate_memory(&allocate_info, None)
+                .context("Failed to allocate memory block")?
+        };
+
+        let host_visible = self.memory_properties.memory_types[memory_type_index as usize]
+            .property_flags
+            .contains(vk::MemoryPropertyFlags::HOST_VISIBLE);
+        let mapped_ptr = if host_visible {
+            let data_ptr = unsafe {
+                self.device
+                    .map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+            };
+            match data_ptr {
+                Ok(data_ptr) => NonNull::new(data_ptr as *mut u8).map(MappedPtr),
+                Err(result) => {
+                    unsafe { self.device.free_memory(memory, None) };
+                    return Err(result).context("Failed to map memory block");
+                }
+            }
+        } else {
+            None
+        };
+
+        let id = state.next_block_id;
+        state.next_block_id += 1;
+        Ok(MemoryBlock {
+            id,
+            memory,
+            size,
+            memory_type_index,
+            kind,
+            mapped_ptr,
+            free_ranges: vec![FreeRange { offset: 0, size }],
+            used_bytes: 0,
+            allocation_count: 0,
+        })
+    }
+
+    fn destroy_block(&self, block: &MemoryBlock) {
+        unsafe {
+            if block.mapped_ptr.is_some() {
+                self.device.unmap_memory(block.memory);
+            }
+            self.device.free_memory(block.memory, None);
+        }
+    }
+}
+
+impl Drop for Allocator {
+    fn drop(&mut self) {
+        self.lock_state()
+            .blocks
+            .iter()
+            .for_each(|block| self.destroy_block(block));
+    }
+}
+
+/// Whether another block with the same memory type and kind as `blocks[index]` is empty too.
+fn has_spare_empty_block(blocks: &[MemoryBlock], index: usize) -> bool {
+    let block = &blocks[index];
+    blocks.iter().enumerate().any(|(other_index, other)| {
+        other_index != index
+            && other.allocation_count == 0
+            && other.memory_type_index == block.memory_type_index
+            && other.kind == block.kind
+    })
+}
+
+/// Vulkan alignments are always powers of two.
+fn align_up(offset: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+    (offset + alignment - 1) & !(alignment - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(size: vk::DeviceSize) -> MemoryBlock {
+        MemoryBlock {
+            id: 0,
+            memory: vk::DeviceMemory::null(),
+            size,
+            memory_type_index: 0,
+            kind: None,
+            mapped_ptr: None,
+            free_ranges: vec![FreeRange { offset: 0, size }],
+            used_bytes: 0,
+            allocation_count: 0,
+        }
+    }
+
+    fn free_ranges(block: &MemoryBlock) -> Vec<(vk::DeviceSize, vk::DeviceSize)> {
+        block
+            .free_ranges
+            .iter()
+            .map(|range| (range.offset, range.size))
+            .collect()
+    }
+
+    #[test]
+    fn allocate_takes_the_first_range_that_fits() {
+        let mut block = block(1024);
+        assert_eq!(block.allocate(256, 1), Some(0));
+        assert_eq!(block.allocate(256, 1), Some(256));
+        assert_eq!(block.allocate(256, 1), Some(512));
+        block.free(0, 256);
+
+        // the hole at the front is too small, so the tail is used
+        assert_eq!(block.allocate(512, 1), None);
+        assert_eq!(block.allocate(300, 1), None);
+        assert_eq!(block.allocate(128, 1), Some(0));
+        assert_eq!(block.allocate(256, 1), Some(768));
+        assert_eq!(free_ranges(&block), vec![(128, 128)]);
+        assert_eq!(block.used_bytes, 896);
+        assert_eq!(block.allocation_count, 4);
+    }
+
+    #[test]
+    fn free_coalesces_with_both_neighbours() {
+        let mut block = block(1024);
+        let offsets = (0..4)
+            .map(|_| block.allocate(256, 1).unwrap())
+            .collect::<Vec<_>>();
+        assert!(block.free_ranges.is_empty());
+
+        block.free(offsets[0], 256);
+        block.free(offsets[2], 256);
+        assert_eq!(free_ranges(&block), vec![(0, 256), (512, 256)]);
+
+        block.free(offsets[1], 256);
+        assert_eq!(free_ranges(&block), vec![(0, 768)]);
+
+        block.free(offsets[3], 256);
+        assert_eq!(free_ranges(&block), vec![(0, 1024)]);
+        assert_eq!(block.used_bytes, 0);
+        assert_eq!(block.allocation_count, 0);
+    }
+
+    #[test]
+    fn allocate_aligns_and_keeps_the_padding_free() {
+        let mut block = block(1024);
+        assert_eq!(block.allocate(10, 1), Some(0));
+        assert_eq!(block.allocate(100, 64), Some(64));
+        assert_eq!(free_ranges(&block), vec![(10, 54), (164, 860)]);
+
+        // the padding is reusable by allocations with a smaller alignment
+        assert_eq!(block.allocate(32, 16), Some(16));
+        assert_eq!(free_ranges(&block), vec![(10, 6), (48, 16), (164, 860)]);
+
+        block.free(16, 32);
+        block.free(64, 100);
+        assert_eq!(free_ranges(&block), vec![(10, 1014)]);
+    }
+
+    #[test]
+    fn allocate_splits_a_range_in_the_middle() {
+        let mut block = block(4096);
+        assert_eq!(block.allocate(1, 1), Some(0));
+        // a granularity-sized alignment leaves a free range on both sides
+        assert_eq!(block.allocate(1024, 1024), Some(1024));
+        assert_eq!(free_ranges(&block), vec![(1, 1023), (2048, 2048)]);
+        assert_eq!(block.allocate(2048, 1024), Some(2048));
+        assert_eq!(free_ranges(&block), vec![(1, 1023)]);
+        assert_eq!(block.allocate(1024, 1024), None);
+    }
+
+    #[test]
+    fn one_empty_block_per_memory_type_and_kind_is_spared() {
+        let mut blocks = (0..4).map(|_| block(1024)).collect::<Vec<_>>();
+        blocks[1].memory_type_index = 1;
+        blocks[2].kind = Some(ResourceKind::Optimal);
+        blocks[3].kind = Some(ResourceKind::Linear);
+        assert!(!has_spare_empty_block(&blocks, 0));
+        assert!(!has_spare_empty_block(&blocks, 1));
+        assert!(!has_spare_empty_block(&blocks, 2));
+
+        blocks[3].kind = Some(ResourceKind::Optimal);
+        assert!(has_spare_empty_block(&blocks, 2));
+        assert!(has_spare_empty_block(&blocks, 3));
+
+        blocks[3].allocate(1, 1);
+        assert!(!has_spare_empty_block(&blocks, 2));
+    }
+
+    #[test]
+    fn align_up_rounds_to_the_next_multiple() {
+        assert_eq!(align_up(0, 256), 0);
+        assert_eq!(align_up(1, 256), 256);
+        assert_eq!(align_up(256, 256), 256);
+        assert_eq!(align_up(257, 1), 257);
+    }
+}
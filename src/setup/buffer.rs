@@ -1,18 +1,75 @@
+use std::{mem::size_of_val, ptr::copy_nonoverlapping, sync::Arc};
+
 use ash::{
     version::{DeviceV1_0, InstanceV1_0},
     vk,
-    vk::{BufferUsageFlags, DeviceSize, MemoryPropertyFlags, PhysicalDevice},
+    vk::{BufferUsageFlags, DeviceSize, MemoryPropertyFlags},
     Device, Instance,
 };
 
+use crate::setup::allocator::{Allocation, Allocator, ResourceKind};
+use crate::setup::error::{SetupError, SetupResult, VkResultExt};
+use crate::setup::queue::Queue;
+
+/// A buffer and the sub-allocation bound to it; both are released when it is dropped.
+pub struct Buffer {
+    pub buffer: vk::Buffer,
+    pub allocation: Allocation,
+    pub size: DeviceSize,
+    allocator: Arc<Allocator>,
+}
+
+impl Buffer {
+    /// Copies `data` to the start of the buffer. The buffer has to live in host-visible memory;
+    /// everything this renderer maps is also host-coherent, so no flush is issued.
+    pub fn write<T: Copy>(&self, data: &[T]) -> SetupResult<()> {
+        let data_ptr = self.host_ptr(size_of_val(data))?;
+        unsafe { copy_nonoverlapping(data.as_ptr() as *const u8, data_ptr, size_of_val(data)) };
+        Ok(())
+    }
+
+    /// Fills `data` from the start of the buffer, the counterpart of `write`.
+    pub fn read<T: Copy>(&self, data: &mut [T]) -> SetupResult<()> {
+        let data_ptr = self.host_ptr(size_of_val(data))?;
+        unsafe {
+            copy_nonoverlapping(
+                data_ptr as *const u8,
+                data.as_mut_ptr() as *mut u8,
+                size_of_val(data),
+            )
+        };
+        Ok(())
+    }
+
+    fn host_ptr(&self, byte_count: usize) -> SetupResult<*mut u8> {
+        if byte_count as DeviceSize > self.size {
+            return Err(SetupError::unsupported(format!(
+                "Cannot access {} bytes of a {} byte buffer!",
+                byte_count, self.size
+            )));
+        }
+        self.allocation
+            .mapped_ptr()
+            .ok_or_else(|| SetupError::unsupported("Buffer memory is not host visible!"))
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe { self.allocator.device().destroy_buffer(self.buffer, None) };
+        if let Err(error) = self.allocator.free(&self.allocation) {
+            eprintln!("Warning: {}", error);
+        }
+    }
+}
+
 pub fn create(
-    instance: &Instance,
-    device: &Device,
-    phys_device: &PhysicalDevice,
+    allocator: &Arc<Allocator>,
     size: DeviceSize,
     usage: BufferUsageFlags,
     properties: MemoryPropertyFlags,
-) -> (vk::Buffer, vk::DeviceMemory) {
+) -> SetupResult<Buffer> {
+    let device = allocator.device();
     let buffer_info = vk::BufferCreateInfo::builder()
         .size(size)
         .usage(usage)
@@ -22,34 +79,35 @@ pub fn create(
     let buffer = unsafe {
         device
             .create_buffer(&buffer_info, None)
-            .expect("Failed to create buffer!")
+            .context("Failed to create buffer")?
     };
     let mem_requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
 
-    let memory_type_index = find_memory_type_index(
-        instance,
-        phys_device,
-        mem_requirements.memory_type_bits,
-        properties,
-    )
-    .expect("failed to find memory type index!");
-    let alloc_info = vk::MemoryAllocateInfo::builder()
-        .allocation_size(mem_requirements.size)
-        .memory_type_index(memory_type_index)
-        .build();
-
-    let buffer_memory = unsafe {
-        device
-            .allocate_memory(&alloc_info, None)
-            .expect("Failed to allocate buffer memory!")
+    let allocation = match allocator.allocate(mem_requirements, properties, ResourceKind::Linear) {
+        Ok(allocation) => allocation,
+        Err(error) => {
+            unsafe { device.destroy_buffer(buffer, None) };
+            return Err(error);
+        }
+    };
+    // from here on dropping the wrapper releases both on failure
+    let buffer = Buffer {
+        buffer,
+        allocation,
+        size,
+        allocator: Arc::clone(allocator),
     };
 
     unsafe {
         device
-            .bind_buffer_memory(buffer, buffer_memory, 0)
-            .expect("Failed to bind buffer memory!")
+            .bind_buffer_memory(
+                buffer.buffer,
+                buffer.allocation.memory,
+                buffer.allocation.offset,
+            )
+            .context("Failed to bind buffer memory")?
     };
-    (buffer, buffer_memory)
+    Ok(buffer)
 }
 
 pub fn find_memory_type_index(
@@ -57,7 +115,7 @@ pub fn find_memory_type_index(
     physical_device: &vk::PhysicalDevice,
     type_filter: u32,
     memory_property_flags: vk::MemoryPropertyFlags,
-) -> Result<u32, &'static str> {
+) -> SetupResult<u32> {
     let physical_device_memory_properties =
         unsafe { instance.get_physical_device_memory_properties(*physical_device) };
 
@@ -73,18 +131,20 @@ pub fn find_memory_type_index(
             return Ok(index);
         }
     }
-    Err("Failed to find suitable memory type for buffer!")
+    Err(SetupError::unsupported(
+        "Failed to find suitable memory type for buffer!",
+    ))
 }
 
 pub fn copy(
     device: &Device,
     command_pool: vk::CommandPool,
-    queue: vk::Queue,
+    queue: &Queue,
     src_buffer: vk::Buffer,
     dst_buffer: vk::Buffer,
     size: vk::DeviceSize,
-) {
-    let command_buffer = begin_single_time_commands(device, command_pool);
+) -> SetupResult<()> {
+    let command_buffer = begin_single_time_commands(device, command_pool)?;
 
     let copy_region = vk::BufferCopy::builder()
         .src_offset(0)
@@ -94,13 +154,13 @@ pub fn copy(
 
     unsafe { device.cmd_copy_buffer(command_buffer, src_buffer, dst_buffer, &[copy_region]) };
 
-    end_single_time_commands(device, command_pool, command_buffer, queue);
+    end_single_time_commands(device, command_pool, command_buffer, queue)
 }
 
 pub fn begin_single_time_commands(
     device: &Device,
     command_pool: vk::CommandPool,
-) -> vk::CommandBuffer {
+) -> SetupResult<vk::CommandBuffer> {
     let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
         .command_pool(command_pool)
         .level(vk::CommandBufferLevel::PRIMARY)
@@ -110,10 +170,10 @@ pub fn begin_single_time_commands(
     let command_buffer = unsafe {
         device
             .allocate_command_buffers(&command_buffer_allocate_info)
-            .expect("Failed to allocate command buffers!")
+            .context("Failed to allocate command buffers")?
             .first()
-            .unwrap()
-            .to_owned()
+            .copied()
+            .ok_or_else(|| SetupError::unsupported("No command buffer was allocated!"))?
     };
 
     let begin_info = vk::CommandBufferBeginInfo::builder()
@@ -123,36 +183,25 @@ pub fn begin_single_time_commands(
     unsafe {
         device
             .begin_command_buffer(command_buffer, &begin_info)
-            .expect("Failed to begin command buffer!")
+            .context("Failed to begin command buffer")?
     };
-    command_buffer
+    Ok(command_buffer)
 }
 
 pub fn end_single_time_commands(
     device: &Device,
     command_pool: vk::CommandPool,
     command_buffer: vk::CommandBuffer,
-    queue: vk::Queue,
-) {
+    queue: &Queue,
+) -> SetupResult<()> {
     unsafe {
         device
             .end_command_buffer(command_buffer)
-            .expect("Failed to end command buffer!")
+            .context("Failed to end command buffer")?
     };
 
-    let submit_info = vk::SubmitInfo::builder()
-        .command_buffers(&[command_buffer])
-        .build();
-
-    unsafe {
-        device
-            .queue_submit(queue, &[submit_info], vk::Fence::null())
-            .expect("Failed to submit to queue!");
+    queue.submit_and_wait(device, command_buffer)?;
 
-        device
-            .queue_wait_idle(queue)
-            .expect("Failed waiting for queue idle!");
-
-        device.free_command_buffers(command_pool, &[command_buffer]);
-    }
+    unsafe { device.free_command_buffers(command_pool, &[command_buffer]) };
+    Ok(())
 }
@@ -1,105 +1,338 @@
 use ash::{version::DeviceV1_0, vk, Device};
+use byteorder::{ByteOrder, NativeEndian};
+use cgmath::{Matrix4, SquareMatrix};
 
-pub fn create(
+use crate::setup::error::{SetupError, SetupResult, VkResultExt};
+use crate::setup::graphics_pipeline::PipelineKind;
+use crate::setup::shader_reflection::ShaderInterface;
+use crate::structs::SubMesh;
+
+const DEFAULT_CLEAR_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+/// Bytes of the `mat4` object transform pushed for every draw.
+const OBJECT_TRANSFORM_SIZE: u32 = 64;
+
+/// One indexed draw of a sub-mesh of the loaded model with its material.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Draw {
+    pub pipeline: PipelineKind,
+    /// Index into `Mesh::sub_meshes`.
+    pub sub_mesh: usize,
+    /// Places this copy of the sub-mesh, applied before the model matrix of the uniform buffer.
+    pub transform: Matrix4<f32>,
+}
+
+/// What a frame renders, rebuilt before each frame is recorded; draws are issued in order.
+#[derive(Clone, Debug)]
+pub struct DrawList {
+    pub clear_color: [f32; 4],
+    pub draws: Vec<Draw>,
+}
+
+impl Default for DrawList {
+    fn default() -> Self {
+        Self {
+            clear_color: DEFAULT_CLEAR_COLOR,
+            draws: vec![],
+        }
+    }
+}
+
+impl DrawList {
+    /// Back to an empty list on the default clear color, keeping the allocation.
+    pub fn reset(&mut self) {
+        self.clear_color = DEFAULT_CLEAR_COLOR;
+        self.draws.clear();
+    }
+
+    pub fn draw(&mut self, pipeline: PipelineKind, sub_mesh: usize) {
+        self.draw_transformed(pipeline, sub_mesh, Matrix4::identity());
+    }
+
+    /// Draws the sub-mesh moved by `transform`, e.g. one of many copies of the model.
+    pub fn draw_transformed(
+        &mut self,
+        pipeline: PipelineKind,
+        sub_mesh: usize,
+        transform: Matrix4<f32>,
+    ) {
+        self.draws.push(Draw {
+            pipeline,
+            sub_mesh,
+            transform,
+        });
+    }
+
+    /// Every sub-mesh with the same pipeline, the whole model.
+    pub fn draw_all(&mut self, pipeline: PipelineKind, sub_mesh_count: usize) {
+        (0..sub_mesh_count).for_each(|sub_mesh| self.draw(pipeline, sub_mesh));
+    }
+
+    /// Checked before recording so a bad list does not leave command buffers half recorded.
+    pub fn validate(&self, sub_mesh_count: usize) -> SetupResult<()> {
+        match self
+            .draws
+            .iter()
+            .find(|draw| draw.sub_mesh >= sub_mesh_count)
+        {
+            Some(draw) => Err(SetupError::unsupported(format!(
+                "Draw list refers to sub-mesh {}, but the model has {}!",
+                draw.sub_mesh, sub_mesh_count
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Command buffers from `command_pool` at the given level.
+pub fn allocate(
     device: &Device,
     command_pool: vk::CommandPool,
-    framebuffers: &[vk::Framebuffer],
-    render_pass: vk::RenderPass,
-    swapchain_extent: vk::Extent2D,
-    pipeline: &vk::Pipeline,
-    pipeline_layout: vk::PipelineLayout,
-    descriptor_sets: &[vk::DescriptorSet],
-    vertex_buffer: vk::Buffer,
-    index_buffer: vk::Buffer,
-    indices: &[u32],
-) -> Vec<vk::CommandBuffer> {
+    level: vk::CommandBufferLevel,
+    count: usize,
+) -> SetupResult<Vec<vk::CommandBuffer>> {
     let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
         .command_pool(command_pool)
-        .level(vk::CommandBufferLevel::PRIMARY)
-        .command_buffer_count(framebuffers.len() as u32)
+        .level(level)
+        .command_buffer_count(count as u32)
         .build();
 
-    let command_buffers = unsafe {
+    unsafe {
         device
             .allocate_command_buffers(&command_buffer_allocate_info)
-            .expect("Failed to allocate command buffers!")
-    };
-    let command_buffers = command_buffers
-        .into_iter()
-        .zip(framebuffers)
-        .zip(descriptor_sets)
-        .map(|((command_buffer, framebuffer), descriptor_set)| {
-            let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder().build();
-
-            unsafe {
-                device
-                    .begin_command_buffer(command_buffer, &command_buffer_begin_info)
-                    .expect("Failed to begin recording command buffer!")
-            };
-
-            let render_area = vk::Rect2D::builder()
-                .offset(vk::Offset2D::builder().x(0).y(0).build())
-                .extent(swapchain_extent)
-                .build();
-
-            let clear_values = [
-                vk::ClearValue {
-                    color: vk::ClearColorValue {
-                        float32: [0.0, 0.0, 0.0, 1.0],
-                    },
-                },
-                vk::ClearValue {
-                    depth_stencil: vk::ClearDepthStencilValue::builder()
-                        .depth(1.0)
-                        .stencil(0)
-                        .build(),
-                },
-            ];
-
-            let mut clear_color_value = vk::ClearColorValue::default();
-            clear_color_value.float32 = [0.0, 0.0, 0.0, 1.0];
-            let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
-                .render_pass(render_pass)
-                .framebuffer(*framebuffer)
-                .render_area(render_area)
-                .clear_values(&clear_values)
-                .build();
-
-            unsafe {
-                device.cmd_begin_render_pass(
-                    command_buffer,
-                    &render_pass_begin_info,
-                    vk::SubpassContents::INLINE,
-                );
-                device.cmd_bind_pipeline(
-                    command_buffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    *pipeline,
-                );
-                device.cmd_bind_vertex_buffers(command_buffer, 0, &[vertex_buffer], &[0]);
-                device.cmd_bind_index_buffer(
-                    command_buffer,
-                    index_buffer,
-                    0,
-                    vk::IndexType::UINT32,
-                );
-                device.cmd_bind_descriptor_sets(
-                    command_buffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    pipeline_layout,
-                    0,
-                    &[*descriptor_set],
-                    &[],
-                );
-                device.cmd_draw_indexed(command_buffer, indices.len() as u32, 1, 0, 0, 0);
-                device.cmd_end_render_pass(command_buffer);
-                device
-                    .end_command_buffer(command_buffer)
-                    .expect("Failed to record command buffer!");
-            };
-            command_buffer
-        })
-        .collect::<Vec<vk::CommandBuffer>>();
-
-    command_buffers
+            .context("Failed to allocate command buffers")
+    }
+}
+
+/// The handles a frame's draws are recorded against; cheap to share with recording threads.
+#[derive(Clone, Debug)]
+pub struct RecordInfo {
+    pub render_pass: vk::RenderPass,
+    pub framebuffer: vk::Framebuffer,
+    pub extent: vk::Extent2D,
+    /// Indexed by `PipelineKind`.
+    pub pipelines: Vec<vk::Pipeline>,
+    pub pipeline_layout: vk::PipelineLayout,
+    /// Indexed by material, for the image behind `framebuffer`.
+    pub material_descriptor_sets: Vec<vk::DescriptorSet>,
+    pub vertex_buffer: vk::Buffer,
+    pub index_buffer: vk::Buffer,
+    /// Whether the vertex shader reads `Draw::transform`; see `reads_object_transform`.
+    pub push_object_transforms: bool,
+}
+
+/// Whether the vertex shader declares the push-constant block `Draw::transform` is written to: a
+/// single `mat4` at offset 0. Other shaders get no push constants from the renderer.
+pub fn reads_object_transform(vertex_shader: &ShaderInterface) -> bool {
+    vertex_shader.push_constants == Some((0, OBJECT_TRANSFORM_SIZE))
+}
+
+/// Records one frame inline into `command_buffer`, whose pool has to be reset since its last
+/// submission completed. `draw_list` has to be validated against `sub_meshes`.
+pub fn record(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    info: &RecordInfo,
+    sub_meshes: &[SubMesh],
+    draw_list: &DrawList,
+) -> SetupResult<()> {
+    begin(
+        device,
+        command_buffer,
+        &vk::CommandBufferBeginInfo::builder().build(),
+    )?;
+    unsafe {
+        begin_render_pass(
+            device,
+            command_buffer,
+            info,
+            draw_list.clear_color,
+            vk::SubpassContents::INLINE,
+        );
+        record_draws(device, command_buffer, info, sub_meshes, &draw_list.draws);
+        device.cmd_end_render_pass(command_buffer);
+    }
+    end(device, command_buffer)
+}
+
+/// Records one frame that runs `secondary_command_buffers` inside the render pass, in order.
+pub fn record_with_secondaries(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    info: &RecordInfo,
+    clear_color: [f32; 4],
+    secondary_command_buffers: &[vk::CommandBuffer],
+) -> SetupResult<()> {
+    begin(
+        device,
+        command_buffer,
+        &vk::CommandBufferBeginInfo::builder().build(),
+    )?;
+    unsafe {
+        begin_render_pass(
+            device,
+            command_buffer,
+            info,
+            clear_color,
+            vk::SubpassContents::SECONDARY_COMMAND_BUFFERS,
+        );
+        if !secondary_command_buffers.is_empty() {
+            device.cmd_execute_commands(command_buffer, secondary_command_buffers);
+        }
+        device.cmd_end_render_pass(command_buffer);
+    }
+    end(device, command_buffer)
+}
+
+/// Records `draws` into a secondary command buffer that continues subpass 0 of the render pass
+/// and framebuffer in `info`.
+pub fn record_secondary(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    info: &RecordInfo,
+    sub_meshes: &[SubMesh],
+    draws: &[Draw],
+) -> SetupResult<()> {
+    let inheritance_info = vk::CommandBufferInheritanceInfo::builder()
+        .render_pass(info.render_pass)
+        .subpass(0)
+        .framebuffer(info.framebuffer)
+        .build();
+    let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+        .flags(
+            vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE
+                | vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        )
+        .inheritance_info(&inheritance_info)
+        .build();
+    begin(device, command_buffer, &command_buffer_begin_info)?;
+    unsafe { record_draws(device, command_buffer, info, sub_meshes, draws) };
+    end(device, command_buffer)
+}
+
+fn begin(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    command_buffer_begin_info: &vk::CommandBufferBeginInfo,
+) -> SetupResult<()> {
+    unsafe {
+        device
+            .begin_command_buffer(command_buffer, command_buffer_begin_info)
+            .context("Failed to begin recording command buffer")
+    }
+}
+
+fn end(device: &Device, command_buffer: vk::CommandBuffer) -> SetupResult<()> {
+    unsafe {
+        device
+            .end_command_buffer(command_buffer)
+            .context("Failed to record command buffer")
+    }
+}
+
+fn render_area(info: &RecordInfo) -> vk::Rect2D {
+    vk::Rect2D::builder()
+        .offset(vk::Offset2D::builder().x(0).y(0).build())
+        .extent(info.extent)
+        .build()
+}
+
+unsafe fn begin_render_pass(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    info: &RecordInfo,
+    clear_color: [f32; 4],
+    contents: vk::SubpassContents,
+) {
+    let clear_values = [
+        vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: clear_color,
+            },
+        },
+        vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue::builder()
+                .depth(1.0)
+                .stencil(0)
+                .build(),
+        },
+    ];
+
+    let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+        .render_pass(info.render_pass)
+        .framebuffer(info.framebuffer)
+        .render_area(render_area(info))
+        .clear_values(&clear_values)
+        .build();
+
+    device.cmd_begin_render_pass(command_buffer, &render_pass_begin_info, contents);
+}
+
+/// Dynamic state is not inherited by secondary command buffers, so every buffer sets its own.
+unsafe fn record_draws(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    info: &RecordInfo,
+    sub_meshes: &[SubMesh],
+    draws: &[Draw],
+) {
+    // every pipeline has the same dynamic state, so it survives pipeline switches
+    device.cmd_set_viewport(
+        command_buffer,
+        0,
+        &[vk::Viewport::builder()
+            .x(0.0)
+            .y(0.0)
+            .width(info.extent.width as f32)
+            .height(info.extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0)
+            .build()],
+    );
+    device.cmd_set_scissor(command_buffer, 0, &[render_area(info)]);
+    device.cmd_bind_vertex_buffers(command_buffer, 0, &[info.vertex_buffer], &[0]);
+    device.cmd_bind_index_buffer(command_buffer, info.index_buffer, 0, vk::IndexType::UINT32);
+
+    let mut bound_pipeline = None;
+    let mut pushed_transform = None;
+    for draw in draws {
+        if bound_pipeline != Some(draw.pipeline) {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                info.pipelines[draw.pipeline.index()],
+            );
+            bound_pipeline = Some(draw.pipeline);
+        }
+        // push constants stay set across pipeline switches, as all pipelines share the layout
+        if info.push_object_transforms && pushed_transform != Some(draw.transform) {
+            let transform: &[f32; 16] = draw.transform.as_ref();
+            let mut constants = [0; OBJECT_TRANSFORM_SIZE as usize];
+            NativeEndian::write_f32_into(transform, &mut constants);
+            device.cmd_push_constants(
+                command_buffer,
+                info.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                &constants,
+            );
+            pushed_transform = Some(draw.transform);
+        }
+        let sub_mesh = &sub_meshes[draw.sub_mesh];
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            info.pipeline_layout,
+            0,
+            &[info.material_descriptor_sets[sub_mesh.material_id]],
+            &[],
+        );
+        device.cmd_draw_indexed(
+            command_buffer,
+            sub_mesh.index_count,
+            1,
+            sub_mesh.first_index,
+            sub_mesh.vertex_offset,
+            0,
+        );
+    }
 }
@@ -5,12 +5,53 @@ use ash::{
 };
 
 use crate::setup::devices::utils::QueueFamilyIndices;
+use crate::setup::error::{SetupResult, VkResultExt};
 
-pub fn create(device: &Device, queue_family_indices: &QueueFamilyIndices) -> vk::CommandPool {
+/// For one-time uploads and readbacks.
+pub fn create(device: &Device, queue_family_indices: &QueueFamilyIndices) -> SetupResult<vk::CommandPool> {
+    create_with_flags(
+        device,
+        queue_family_indices,
+        vk::CommandPoolCreateFlags::empty(),
+    )
+}
+
+/// One pool per frame in flight for command buffers that are re-recorded every frame; the whole
+/// pool is reset once the frame's fence signals, which is cheaper than resetting buffers one by one.
+pub fn create_per_frame(
+    device: &Device,
+    queue_family_indices: &QueueFamilyIndices,
+    frames_in_flight: usize,
+) -> SetupResult<Vec<vk::CommandPool>> {
+    let mut command_pools = Vec::with_capacity(frames_in_flight);
+    for _ in 0..frames_in_flight {
+        match create_with_flags(
+            device,
+            queue_family_indices,
+            vk::CommandPoolCreateFlags::TRANSIENT,
+        ) {
+            Ok(command_pool) => command_pools.push(command_pool),
+            Err(error) => {
+                command_pools.iter().for_each(|command_pool| unsafe {
+                    device.destroy_command_pool(*command_pool, None)
+                });
+                return Err(error);
+            }
+        }
+    }
+    Ok(command_pools)
+}
+
+fn create_with_flags(
+    device: &Device,
+    queue_family_indices: &QueueFamilyIndices,
+    flags: vk::CommandPoolCreateFlags,
+) -> SetupResult<vk::CommandPool> {
     let command_pool_create_info = vk::CommandPoolCreateInfo::builder()
+        .flags(flags)
         .queue_family_index(queue_family_indices.graphics)
         .build();
 
-    let command_pool = unsafe { device.create_command_pool(&command_pool_create_info, None).expect("Failed to create command pool!") };
-    command_pool
+    let command_pool = unsafe { device.create_command_pool(&command_pool_create_info, None).context("Failed to create command pool")? };
+    Ok(command_pool)
 }
\ No newline at end of file
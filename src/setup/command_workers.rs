@@ -0,0 +1,195 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use ash::{version::DeviceV1_0, vk, Device};
+
+use crate::setup::command_buffers::{self, Draw, RecordInfo};
+use crate::setup::command_pool;
+use crate::setup::devices::utils::QueueFamilyIndices;
+use crate::setup::error::{SetupError, SetupResult, VkResultExt};
+use crate::structs::SubMesh;
+
+/// A slice of one frame's draw list for a worker to record.
+struct Job {
+    frame_index: usize,
+    info: Arc<RecordInfo>,
+    draws: Vec<Draw>,
+}
+
+struct Worker {
+    jobs: Option<Sender<Job>>,
+    results: Receiver<SetupResult<vk::CommandBuffer>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Threads recording secondary command buffers in parallel. Command pools are externally
+/// synchronized, so every worker owns one per frame in flight and records only from those.
+pub struct CommandWorkers {
+    workers: Vec<Worker>,
+}
+
+impl CommandWorkers {
+    /// `sub_meshes` are the ones every draw list is validated against. At least one worker is
+    /// needed; recording without workers happens inline instead.
+    pub fn new(
+        device: &Arc<Device>,
+        queue_family_indices: &QueueFamilyIndices,
+        frames_in_flight: usize,
+        worker_count: usize,
+        sub_meshes: &[SubMesh],
+    ) -> SetupResult<Self> {
+        if worker_count == 0 {
+            return Err(SetupError::unsupported(
+                "Command workers need at least one recording thread!",
+            ));
+        }
+        let sub_meshes: Arc<[SubMesh]> = Arc::from(sub_meshes);
+        let mut workers = Self {
+            workers: Vec::with_capacity(worker_count),
+        };
+        for _ in 0..worker_count {
+            // dropping `workers` on error stops the threads already started, which free their pools
+            let command_pools =
+                create_command_pools(device, queue_family_indices, frames_in_flight)?;
+            let (job_sender, jobs) = mpsc::channel();
+            let (result_sender, results) = mpsc::channel();
+            let device = Arc::clone(device);
+            let sub_meshes = Arc::clone(&sub_meshes);
+            let thread = thread::spawn(move || {
+                run_worker(&device, &command_pools, &sub_meshes, jobs, result_sender)
+            });
+            workers.workers.push(Worker {
+                jobs: Some(job_sender),
+                results,
+                thread: Some(thread),
+            });
+        }
+        Ok(workers)
+    }
+
+    /// Splits `draws` into consecutive slices, one per worker, and returns their secondary
+    /// command buffers in draw order. The frame slot's previous submission must have completed.
+    pub fn record(
+        &self,
+        frame_index: usize,
+        info: RecordInfo,
+        draws: &[Draw],
+    ) -> SetupResult<Vec<vk::CommandBuffer>> {
+        if draws.is_empty() {
+            return Ok(vec![]);
+        }
+        let info = Arc::new(info);
+        let slice_len = draws.len().div_ceil(self.workers.len());
+        let mut busy_workers = Vec::with_capacity(self.workers.len());
+        for (worker, draws) in self.workers.iter().zip(draws.chunks(slice_len)) {
+            let job = Job {
+                frame_index,
+                info: Arc::clone(&info),
+                draws: draws.to_vec(),
+            };
+            let sent = worker
+                .jobs
+                .as_ref()
+                .is_some_and(|jobs| jobs.send(job).is_ok());
+            busy_workers.push((worker, sent));
+        }
+
+        // every result is collected so none is left over for the next frame
+        let results = busy_workers
+            .into_iter()
+            .map(|(worker, sent)| {
+                if sent {
+                    worker
+                        .results
+                        .recv()
+                        .unwrap_or_else(|_| Err(worker_stopped()))
+                } else {
+                    Err(worker_stopped())
+                }
+            })
+            .collect::<Vec<_>>();
+        results.into_iter().collect()
+    }
+}
+
+impl Drop for CommandWorkers {
+    fn drop(&mut self) {
+        // closing the job channels ends the loops; the threads destroy their pools on the way out
+        self.workers
+            .iter_mut()
+            .for_each(|worker| worker.jobs = None);
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// One pool per frame in flight, each with the single secondary buffer recorded into it.
+fn create_command_pools(
+    device: &Device,
+    queue_family_indices: &QueueFamilyIndices,
+    frames_in_flight: usize,
+) -> SetupResult<Vec<(vk::CommandPool, vk::CommandBuffer)>> {
+    let command_pools =
+        command_pool::create_per_frame(device, queue_family_indices, frames_in_flight)?;
+    let command_buffers = command_pools
+        .iter()
+        .map(|command_pool| {
+            command_buffers::allocate(device, *command_pool, vk::CommandBufferLevel::SECONDARY, 1)
+                .map(|command_buffers| command_buffers[0])
+        })
+        .collect::<SetupResult<Vec<vk::CommandBuffer>>>();
+    match command_buffers {
+        Ok(command_buffers) => Ok(command_pools.into_iter().zip(command_buffers).collect()),
+        Err(error) => {
+            command_pools.iter().for_each(|command_pool| unsafe {
+                device.destroy_command_pool(*command_pool, None)
+            });
+            Err(error)
+        }
+    }
+}
+
+fn run_worker(
+    device: &Device,
+    command_pools: &[(vk::CommandPool, vk::CommandBuffer)],
+    sub_meshes: &[SubMesh],
+    jobs: Receiver<Job>,
+    results: Sender<SetupResult<vk::CommandBuffer>>,
+) {
+    for job in jobs {
+        let (command_pool, command_buffer) = command_pools[job.frame_index];
+        let result = unsafe {
+            device
+                .reset_command_pool(command_pool, vk::CommandPoolResetFlags::empty())
+                .context("Failed to reset command pool")
+        }
+        .and_then(|_| {
+            command_buffers::record_secondary(
+                device,
+                command_buffer,
+                &job.info,
+                sub_meshes,
+                &job.draws,
+            )
+        })
+        .map(|_| command_buffer);
+        if results.send(result).is_err() {
+            break;
+        }
+    }
+    command_pools
+        .iter()
+        .for_each(|(command_pool, _)| unsafe { device.destroy_command_pool(*command_pool, None) });
+}
+
+fn worker_stopped() -> SetupError {
+    SetupError::unsupported("A command recording thread stopped unexpectedly!")
+}
@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use ash::{
+    extensions::{ext::DebugUtils, khr::Surface},
+    version::{DeviceV1_0, InstanceV1_0},
+    vk, Device, Entry, Instance,
+};
+use winit::window::Window;
+
+use crate::setup::{
+    self, devices::utils::QueueFamilyIndices, error::SetupResult, queue::TimelineSupport,
+};
+
+/// Instance, surface and logical device shared by every other Vulkan object.
+/// Resources hold a clone of `device` and release themselves on drop, so the context has to be
+/// dropped after all of them; it then destroys the device, surface and instance in that order.
+pub struct VulkanContext {
+    pub entry: Entry,
+    pub instance: Instance,
+    pub debug_utils: Option<DebugUtils>,
+    pub debug_utils_messenger_ext: Option<vk::DebugUtilsMessengerEXT>,
+    pub surface: Option<Surface>,
+    pub surface_khr: Option<vk::SurfaceKHR>,
+    pub physical_device: vk::PhysicalDevice,
+    /// Sample counts usable for both color and depth attachments, always including 1.
+    pub msaa_sample_counts: vk::SampleCountFlags,
+    /// False when validation was requested but the layers are not available.
+    pub validation_enabled: bool,
+    /// `None` when timeline semaphores were not requested or the device lacks them.
+    pub timeline_support: Option<TimelineSupport>,
+    pub device: Arc<Device>,
+    pub queue_family_indices: QueueFamilyIndices,
+}
+
+impl VulkanContext {
+    /// Without a window no surface is created and the device is picked for offscreen rendering.
+    pub fn new(
+        window: Option<&Window>,
+        enable_validation_layers: bool,
+        enable_timeline_semaphores: bool,
+    ) -> SetupResult<Self> {
+        let headless = window.is_none();
+        let (entry, instance, validation_enabled, instance_version) =
+            setup::instance::create(enable_validation_layers, headless)?;
+        let (debug_utils, debug_utils_messenger_ext) =
+            setup::validation_layers::initialize(&entry, &instance, validation_enabled)?;
+        let (surface, surface_khr) = match window {
+            Some(window) => (
+                Some(Surface::new(&entry, &instance)),
+                Some(setup::platform::surface_khr::create(
+                    &entry, &instance, window,
+                )?),
+            ),
+            None => (None, None),
+        };
+        let surface_pair = surface.as_ref().zip(surface_khr);
+        let (physical_device, msaa_sample_counts) =
+            setup::devices::physical::select(&instance, surface_pair)?;
+        let timeline_support = if enable_timeline_semaphores {
+            setup::devices::physical::timeline_semaphore_support(
+                &instance,
+                physical_device,
+                instance_version,
+            )?
+        } else {
+            None
+        };
+        let (device, queue_family_indices) = setup::devices::logical::create(
+            &instance,
+            physical_device,
+            surface_pair,
+            validation_enabled,
+            timeline_support,
+        )?;
+
+        Ok(VulkanContext {
+            entry,
+            instance,
+            debug_utils,
+            debug_utils_messenger_ext,
+            surface,
+            surface_khr,
+            physical_device,
+            msaa_sample_counts,
+            validation_enabled,
+            timeline_support,
+            device: Arc::new(device),
+            queue_family_indices,
+        })
+    }
+
+    pub fn surface_pair(&self) -> Option<(&Surface, vk::SurfaceKHR)> {
+        self.surface.as_ref().zip(self.surface_khr)
+    }
+}
+
+impl Drop for VulkanContext {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_device(None);
+
+            if let (Some(debug_utils), Some(debug_utils_messenger_ext)) =
+                (self.debug_utils.as_ref(), self.debug_utils_messenger_ext)
+            {
+                debug_utils.destroy_debug_utils_messenger(debug_utils_messenger_ext, None)
+            }
+
+            if let (Some(surface), Some(surface_khr)) = (self.surface.as_ref(), self.surface_khr) {
+                surface.destroy_surface(surface_khr, None);
+            }
+            self.instance.destroy_instance(None);
+        }
+    }
+}
@@ -1,28 +1,35 @@
 use std::{collections::HashSet, os::raw::c_char};
 
-use ash::{extensions::khr::Surface, version::InstanceV1_0, vk, Instance};
+use ash::{
+    extensions::khr::{Surface, TimelineSemaphore},
+    version::InstanceV1_0,
+    vk, Instance,
+};
 
-use crate::setup::{devices::utils, extensions, validation_layers::utils as debug_utils};
+use crate::setup::{
+    devices::utils,
+    error::{SetupError, SetupResult, VkResultExt},
+    extensions,
+    queue::TimelineSupport,
+    validation_layers::utils as debug_utils,
+};
 
+/// Enables timeline semaphores when `timeline_support` is given, as found by
+/// `physical::timeline_semaphore_support`.
 pub fn create(
     instance: &Instance,
     physical_device: vk::PhysicalDevice,
-    surface: &Surface,
-    surface_khr: vk::SurfaceKHR,
+    surface: Option<(&Surface, vk::SurfaceKHR)>,
     enable_validation_layers: bool,
-) -> (ash::Device, utils::QueueFamilyIndices) {
-    let queue_family_indices = utils::get_physical_device_queue_family_indices(
-        instance,
-        physical_device,
-        surface,
-        surface_khr,
-    )
-    .expect("No queue families contain required flags!");
-    let unique_queue_family_indices: HashSet<u32> =
-        [queue_family_indices.graphics, queue_family_indices.present]
-            .iter()
-            .cloned()
-            .collect();
+    timeline_support: Option<TimelineSupport>,
+) -> SetupResult<(ash::Device, utils::QueueFamilyIndices)> {
+    let queue_family_indices =
+        utils::get_physical_device_queue_family_indices(instance, physical_device, surface)?
+            .ok_or_else(|| SetupError::unsupported("No queue families contain required flags!"))?;
+    let unique_queue_family_indices: HashSet<u32> = Some(queue_family_indices.graphics)
+        .into_iter()
+        .chain(queue_family_indices.present)
+        .collect();
     let queue_priorities: [f32; 1] = [1.0];
 
     let queue_create_infos = unique_queue_family_indices
@@ -35,8 +42,12 @@ pub fn create(
         })
         .collect::<Vec<vk::DeviceQueueCreateInfo>>();
 
+    // wireframe pipelines and sample shading are optional and only used where supported
+    let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
     let device_features = vk::PhysicalDeviceFeatures::builder()
         .sampler_anisotropy(true)
+        .fill_mode_non_solid(supported_features.fill_mode_non_solid == vk::TRUE)
+        .sample_rate_shading(supported_features.sample_rate_shading == vk::TRUE)
         .build();
 
     // variables below in main function body to prevent getting destroyed before entry.create_instance()
@@ -45,7 +56,13 @@ pub fn create(
         .iter()
         .map(|layer_name| layer_name.as_ptr())
         .collect();
-    let enabled_extension_names = extensions::get_device_extensions();
+    let mut enabled_extension_names = extensions::get_device_extensions(surface.is_none());
+    if timeline_support == Some(TimelineSupport::Extension) {
+        enabled_extension_names.push(TimelineSemaphore::name().as_ptr());
+    }
+    let mut timeline_semaphore_features = vk::PhysicalDeviceTimelineSemaphoreFeatures::builder()
+        .timeline_semaphore(true)
+        .build();
 
     let mut create_info_builder = vk::DeviceCreateInfo::builder()
         .queue_create_infos(&queue_create_infos)
@@ -54,12 +71,15 @@ pub fn create(
     if enable_validation_layers {
         create_info_builder = create_info_builder.enabled_layer_names(&enabled_layer_names);
     }
+    if timeline_support.is_some() {
+        create_info_builder = create_info_builder.push_next(&mut timeline_semaphore_features);
+    }
     let create_info = create_info_builder.build();
 
     let device = unsafe {
         instance
             .create_device(physical_device, &create_info, None)
-            .expect("Failed to create logical device!")
+            .context("Failed to create logical device")?
     };
-    (device, queue_family_indices)
+    Ok((device, queue_family_indices))
 }
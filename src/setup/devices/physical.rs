@@ -1,66 +1,158 @@
-use ash::{extensions::khr::Surface, version::InstanceV1_0, vk, Instance};
+use std::ffi::c_void;
 
-use crate::setup::devices::utils;
+use ash::{
+    extensions::khr::{Surface, TimelineSemaphore},
+    version::{InstanceV1_0, InstanceV1_1},
+    vk, Instance,
+};
 
+use crate::setup::{
+    devices::utils,
+    error::{SetupError, SetupResult, VkResultExt},
+    queue::TimelineSupport,
+};
+
+/// Picks the first suitable device; without a surface (headless) presentation support is not required.
 pub fn select(
     instance: &Instance,
-    surface: &Surface,
-    surface_khr: vk::SurfaceKHR,
-) -> (vk::PhysicalDevice, vk::SampleCountFlags) {
+    surface: Option<(&Surface, vk::SurfaceKHR)>,
+) -> SetupResult<(vk::PhysicalDevice, vk::SampleCountFlags)> {
     let physical_devices = unsafe {
         instance
             .enumerate_physical_devices()
-            .expect("Failed to enumerate physical devices")
+            .context("Failed to enumerate physical devices")?
     };
 
     if physical_devices.is_empty() {
-        panic!("No physical devices with Vulkan support!");
+        return Err(SetupError::unsupported(
+            "No physical devices with Vulkan support!",
+        ));
     }
 
-    let suitable_devices = physical_devices
-        .into_iter()
-        .filter(|device| {
-            utils::is_physical_device_suitable(instance, *device, surface, surface_khr)
-        })
-        .collect::<Vec<vk::PhysicalDevice>>();
-
-    if suitable_devices.is_empty() {
-        panic!("No suitable devices found!")
+    let mut suitable_devices = Vec::with_capacity(physical_devices.len());
+    for device in physical_devices {
+        if utils::is_physical_device_suitable(instance, device, surface)? {
+            suitable_devices.push(device);
+        }
     }
 
     let device = suitable_devices
         .first()
-        .expect("Failed to retrieve suitable physical device!")
+        .ok_or_else(|| SetupError::unsupported("No suitable devices found!"))?
         .to_owned();
-    (device, get_max_usable_sample_count(instance, &device))
+    Ok((device, get_usable_sample_counts(instance, &device)))
+}
+
+/// Core timeline semaphores need Vulkan 1.2 on both the instance and the device; older devices
+/// may have the extension instead. Either way the feature query needs Vulkan 1.1.
+pub fn timeline_semaphore_support(
+    instance: &Instance,
+    device: vk::PhysicalDevice,
+    instance_version: u32,
+) -> SetupResult<Option<TimelineSupport>> {
+    let device_version = unsafe { instance.get_physical_device_properties(device) }.api_version;
+    let version = instance_version.min(device_version);
+    let support = if version >= vk::make_version(1, 2, 0) {
+        TimelineSupport::Core
+    } else if version >= vk::make_version(1, 1, 0)
+        && utils::is_device_extension_supported(instance, device, TimelineSemaphore::name())?
+    {
+        TimelineSupport::Extension
+    } else {
+        return Ok(None);
+    };
+
+    let mut timeline_semaphore_features = vk::PhysicalDeviceTimelineSemaphoreFeatures::default();
+    // this ash version has no `push_next` on `PhysicalDeviceFeatures2`
+    let mut features = vk::PhysicalDeviceFeatures2 {
+        p_next: &mut timeline_semaphore_features as *mut _ as *mut c_void,
+        ..Default::default()
+    };
+    unsafe { instance.get_physical_device_features2(device, &mut features) };
+    Ok(Some(support).filter(|_| timeline_semaphore_features.timeline_semaphore == vk::TRUE))
+}
+
+/// Largest of `sample_counts`, as returned by `get_usable_sample_counts`, that does not exceed
+/// `requested`; 1 when there is none.
+pub fn clamp_sample_count(
+    sample_counts: vk::SampleCountFlags,
+    requested: u32,
+) -> vk::SampleCountFlags {
+    // each flag's bit value is its sample count, so the mask keeps the counts up to the highest
+    // power of two in `requested`
+    let highest_requested_bit = match requested {
+        0 => 0,
+        requested => 1u64 << (31 - requested.leading_zeros()),
+    };
+    let not_above_requested = (highest_requested_bit << 1).saturating_sub(1);
+    let candidates = u64::from(sample_counts.as_raw()) & not_above_requested;
+    match candidates {
+        0 => vk::SampleCountFlags::TYPE_1,
+        candidates => vk::SampleCountFlags::from_raw(1 << (63 - candidates.leading_zeros())),
+    }
 }
 
-pub fn get_max_usable_sample_count(
+/// The smallest of `sample_counts` above `current`, or 1 after the largest.
+pub fn next_sample_count(
+    sample_counts: vk::SampleCountFlags,
+    current: vk::SampleCountFlags,
+) -> vk::SampleCountFlags {
+    let above_current = !((current.as_raw() << 1) - 1);
+    match sample_counts.as_raw() & above_current {
+        0 => vk::SampleCountFlags::TYPE_1,
+        candidates => vk::SampleCountFlags::from_raw(1 << candidates.trailing_zeros()),
+    }
+}
+
+/// Sample counts usable for both color and depth attachments; the spec does not require them to
+/// be contiguous.
+pub fn get_usable_sample_counts(
     instance: &Instance,
     device: &vk::PhysicalDevice,
 ) -> vk::SampleCountFlags {
     let properties = unsafe { instance.get_physical_device_properties(*device) };
-    let counts = properties.limits.framebuffer_color_sample_counts
-        & properties.limits.framebuffer_depth_sample_counts;
+    properties.limits.framebuffer_color_sample_counts
+        & properties.limits.framebuffer_depth_sample_counts
+        | vk::SampleCountFlags::TYPE_1
+}
 
-    if counts.contains(vk::SampleCountFlags::TYPE_64) {
-        return vk::SampleCountFlags::TYPE_64;
-    }
-    if counts.contains(vk::SampleCountFlags::TYPE_32) {
-        return vk::SampleCountFlags::TYPE_32;
-    }
-    if counts.contains(vk::SampleCountFlags::TYPE_16) {
-        return vk::SampleCountFlags::TYPE_16;
-    }
-    if counts.contains(vk::SampleCountFlags::TYPE_8) {
-        return vk::SampleCountFlags::TYPE_8;
-    }
-    if counts.contains(vk::SampleCountFlags::TYPE_4) {
-        return vk::SampleCountFlags::TYPE_4;
-    }
-    if counts.contains(vk::SampleCountFlags::TYPE_2) {
-        return vk::SampleCountFlags::TYPE_2;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_counts_round_down_to_a_supported_count() {
+        let counts = vk::SampleCountFlags::TYPE_1
+            | vk::SampleCountFlags::TYPE_2
+            | vk::SampleCountFlags::TYPE_4
+            | vk::SampleCountFlags::TYPE_8;
+        let clamp = |requested| clamp_sample_count(counts, requested).as_raw();
+        assert_eq!(clamp(4), 4);
+        assert_eq!(clamp(6), 4);
+        assert_eq!(clamp(8), 8);
+        assert_eq!(clamp(64), 8);
+        assert_eq!(clamp(u32::MAX), 8);
+        assert_eq!(clamp(1), 1);
+        assert_eq!(clamp(0), 1);
+        assert_eq!(
+            clamp_sample_count(vk::SampleCountFlags::TYPE_1, 4).as_raw(),
+            1
+        );
     }
 
-    vk::SampleCountFlags::TYPE_1
+    #[test]
+    fn unsupported_counts_between_supported_ones_are_skipped() {
+        let counts = vk::SampleCountFlags::TYPE_1
+            | vk::SampleCountFlags::TYPE_4
+            | vk::SampleCountFlags::TYPE_16;
+        let clamp = |requested| clamp_sample_count(counts, requested).as_raw();
+        assert_eq!(clamp(2), 1);
+        assert_eq!(clamp(8), 4);
+        assert_eq!(clamp(32), 16);
+
+        let next = |current| next_sample_count(counts, vk::SampleCountFlags::from_raw(current));
+        assert_eq!(next(1).as_raw(), 4);
+        assert_eq!(next(4).as_raw(), 16);
+        assert_eq!(next(16).as_raw(), 1);
+    }
 }
@@ -2,52 +2,49 @@ use std::ffi::{CStr, CString};
 
 use ash::{extensions::khr::Surface, version::InstanceV1_0, vk, Instance};
 
-use crate::setup::{extensions, swapchain};
+use crate::setup::{
+    error::{SetupResult, VkResultExt},
+    extensions, swapchain,
+};
 
 pub fn is_physical_device_suitable(
     instance: &Instance,
     device: vk::PhysicalDevice,
-    surface: &Surface,
-    surface_khr: vk::SurfaceKHR,
-) -> bool {
-    let supports_required_extensions = check_device_extension_support(instance, device);
+    surface: Option<(&Surface, vk::SurfaceKHR)>,
+) -> SetupResult<bool> {
+    let supports_required_extensions =
+        check_device_extension_support(instance, device, surface.is_none())?;
     let supports_required_queue_families =
-        get_physical_device_queue_family_indices(instance, device, surface, surface_khr).is_some();
-
-    let swap_chain_is_adequate = if supports_required_extensions {
-        let swap_chain_details =
-            swapchain::utils::query_swapchain_support(device, surface, surface_khr);
-        !swap_chain_details.formats.is_empty() && !swap_chain_details.present_modes.is_empty()
-    } else {
-        false
+        get_physical_device_queue_family_indices(instance, device, surface)?.is_some();
+
+    // headless rendering never presents, so there is no swapchain to be adequate for
+    let swap_chain_is_adequate = match surface {
+        Some((surface, surface_khr)) if supports_required_extensions => {
+            let swap_chain_details =
+                swapchain::utils::query_swapchain_support(device, surface, surface_khr)?;
+            !swap_chain_details.formats.is_empty() && !swap_chain_details.present_modes.is_empty()
+        }
+        Some(_) => false,
+        None => true,
     };
 
     let supported_features = unsafe { instance.get_physical_device_features(device) };
     let supports_anisotropy = supported_features.sampler_anisotropy > 0;
 
-    supports_required_queue_families
+    Ok(supports_required_queue_families
         && supports_required_extensions
         && swap_chain_is_adequate
-        && supports_anisotropy
+        && supports_anisotropy)
 }
 
-pub fn check_device_extension_support(instance: &Instance, device: vk::PhysicalDevice) -> bool {
+pub fn check_device_extension_support(
+    instance: &Instance,
+    device: vk::PhysicalDevice,
+    headless: bool,
+) -> SetupResult<bool> {
     // TODO improve extension name generation; this feels overly complicated ¯\_(ツ)_/¯
-    let supported_extensions = unsafe {
-        instance
-            .enumerate_device_extension_properties(device)
-            .expect("Failed to request supported device extensions")
-    };
-    let supported_extension_names = supported_extensions
-        .into_iter()
-        .map(|properties| unsafe {
-            CStr::from_ptr(properties.extension_name.as_ptr())
-                .to_owned()
-                .into_boxed_c_str()
-                .into_c_string()
-        })
-        .collect::<Vec<CString>>();
-    let mut required_extension_names = extensions::get_device_extensions()
+    let supported_extension_names = get_supported_extension_names(instance, device)?;
+    let mut required_extension_names = extensions::get_device_extensions(headless)
         .into_iter()
         .map(|extension_name| unsafe {
             CStr::from_ptr(extension_name)
@@ -60,12 +57,43 @@ pub fn check_device_extension_support(instance: &Instance, device: vk::PhysicalD
     required_extension_names.retain(|required_extension_name| {
         !supported_extension_names.contains(required_extension_name)
     });
-    required_extension_names.len() == 0
+    Ok(required_extension_names.is_empty())
+}
+
+pub fn is_device_extension_supported(
+    instance: &Instance,
+    device: vk::PhysicalDevice,
+    extension_name: &CStr,
+) -> SetupResult<bool> {
+    Ok(get_supported_extension_names(instance, device)?
+        .iter()
+        .any(|supported_extension_name| supported_extension_name.as_c_str() == extension_name))
+}
+
+fn get_supported_extension_names(
+    instance: &Instance,
+    device: vk::PhysicalDevice,
+) -> SetupResult<Vec<CString>> {
+    let supported_extensions = unsafe {
+        instance
+            .enumerate_device_extension_properties(device)
+            .context("Failed to request supported device extensions")?
+    };
+    Ok(supported_extensions
+        .into_iter()
+        .map(|properties| unsafe {
+            CStr::from_ptr(properties.extension_name.as_ptr())
+                .to_owned()
+                .into_boxed_c_str()
+                .into_c_string()
+        })
+        .collect())
 }
 
 pub struct QueueFamilyIndices {
     pub graphics: u32,
-    pub present: u32,
+    /// `None` when no surface was provided, i.e. when rendering headless.
+    pub present: Option<u32>,
 }
 
 struct InnerQueueFamilyIndices {
@@ -74,56 +102,53 @@ struct InnerQueueFamilyIndices {
 }
 
 impl InnerQueueFamilyIndices {
-    fn is_complete(&self) -> bool {
-        self.graphics.is_some() && self.present.is_some()
+    fn is_complete(&self, requires_present: bool) -> bool {
+        self.graphics.is_some() && (!requires_present || self.present.is_some())
     }
 }
 
 pub fn get_physical_device_queue_family_indices(
     instance: &Instance,
     physical_device: vk::PhysicalDevice,
-    surface: &Surface,
-    surface_khr: vk::SurfaceKHR,
-) -> Option<QueueFamilyIndices> {
+    surface: Option<(&Surface, vk::SurfaceKHR)>,
+) -> SetupResult<Option<QueueFamilyIndices>> {
     let queue_family_properties_vec =
         unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
     let indices = 0..queue_family_properties_vec.len() as u32;
 
-    let inner_queue_family_indices = queue_family_properties_vec.into_iter().zip(indices).fold(
-        InnerQueueFamilyIndices {
-            graphics: None,
-            present: None,
-        },
-        |mut indices, (queue_family_properties, queue_index)| {
-            if indices.graphics.is_none()
-                && queue_family_properties.queue_count > 0
-                && queue_family_properties
-                    .queue_flags
-                    .contains(vk::QueueFlags::GRAPHICS)
-            {
-                indices.graphics = Some(queue_index);
-            }
-
-            let supports_surface = unsafe {
+    let mut inner_queue_family_indices = InnerQueueFamilyIndices {
+        graphics: None,
+        present: None,
+    };
+    for (queue_family_properties, queue_index) in
+        queue_family_properties_vec.into_iter().zip(indices)
+    {
+        if inner_queue_family_indices.graphics.is_none()
+            && queue_family_properties.queue_count > 0
+            && queue_family_properties
+                .queue_flags
+                .contains(vk::QueueFlags::GRAPHICS)
+        {
+            inner_queue_family_indices.graphics = Some(queue_index);
+        }
+
+        let supports_surface = match surface {
+            Some((surface, surface_khr)) => unsafe {
                 surface
                     .get_physical_device_surface_support(physical_device, queue_index, surface_khr)
-                    .expect("Failed to get physical device surface support!")
-            };
-            if indices.present.is_none() && supports_surface {
-                indices.present = Some(queue_index);
-            }
-
-            indices
-        },
-    );
+                    .context("Failed to get physical device surface support")?
+            },
+            None => false,
+        };
+        if inner_queue_family_indices.present.is_none() && supports_surface {
+            inner_queue_family_indices.present = Some(queue_index);
+        }
+    }
 
+    let complete = inner_queue_family_indices.is_complete(surface.is_some());
     let InnerQueueFamilyIndices { graphics, present } = inner_queue_family_indices;
-    if inner_queue_family_indices.is_complete() {
-        Some(QueueFamilyIndices {
-            graphics: graphics.unwrap(),
-            present: present.unwrap(),
-        })
-    } else {
-        None
+    match graphics {
+        Some(graphics) if complete => Ok(Some(QueueFamilyIndices { graphics, present })),
+        _ => Ok(None),
     }
 }
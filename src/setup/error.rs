@@ -0,0 +1,156 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+use ash::{prelude::VkResult, vk, InstanceError, LoadingError};
+
+pub type SetupResult<T> = Result<T, SetupError>;
+
+#[derive(Debug)]
+pub enum SetupError {
+    /// The Vulkan loader library could not be loaded.
+    Loading(LoadingError),
+    /// `vkCreateInstance` failed or instance-level functions could not be loaded.
+    Instance(InstanceError),
+    /// A Vulkan call returned an error code; `context` describes what was being attempted.
+    Vulkan {
+        context: &'static str,
+        result: vk::Result,
+    },
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    Obj {
+        path: PathBuf,
+        source: tobj::LoadError,
+    },
+    Gltf {
+        path: PathBuf,
+        source: gltf::Error,
+    },
+    /// A SPIR-V module is malformed or does not match what the renderer provides.
+    Shader {
+        path: PathBuf,
+        message: String,
+    },
+    /// A shader source does not compile; `message` holds the compiler's `file:line: error` output.
+    ShaderCompilation {
+        path: PathBuf,
+        message: String,
+    },
+    /// Shader sources cannot be watched for changes.
+    #[cfg(feature = "shader-compiler")]
+    Watch {
+        path: PathBuf,
+        source: notify::Error,
+    },
+    /// The config file is not valid TOML or has unknown or mistyped keys.
+    Config {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    /// A command-line option has a value that does not parse.
+    Argument {
+        name: String,
+        value: String,
+    },
+    /// The device, driver or input lacks something the renderer requires.
+    Unsupported(String),
+}
+
+impl SetupError {
+    pub fn unsupported<S: Into<String>>(message: S) -> Self {
+        SetupError::Unsupported(message.into())
+    }
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SetupError::Loading(error) => write!(f, "Failed to load Vulkan library: {}", error),
+            SetupError::Instance(error) => write!(f, "Failed to create Vulkan instance: {}", error),
+            SetupError::Vulkan { context, result } => write!(f, "{}: {}", context, result),
+            SetupError::Io { path, source } => {
+                write!(f, "Failed to read {}: {}", path.display(), source)
+            }
+            SetupError::Image { path, source } => {
+                write!(f, "Failed to load image {}: {}", path.display(), source)
+            }
+            SetupError::Obj { path, source } => {
+                write!(f, "Failed to load model {}: {}", path.display(), source)
+            }
+            SetupError::Gltf { path, source } => {
+                write!(f, "Failed to load model {}: {}", path.display(), source)
+            }
+            SetupError::Shader { path, message } => {
+                write!(f, "Invalid shader {}: {}", path.display(), message)
+            }
+            SetupError::ShaderCompilation { path, message } => {
+                write!(
+                    f,
+                    "Failed to compile {}:\n{}",
+                    path.display(),
+                    message.trim_end()
+                )
+            }
+            #[cfg(feature = "shader-compiler")]
+            SetupError::Watch { path, source } => {
+                write!(f, "Failed to watch {}: {}", path.display(), source)
+            }
+            SetupError::Config { path, source } => {
+                write!(f, "Failed to parse config {}: {}", path.display(), source)
+            }
+            SetupError::Argument { name, value } => {
+                write!(f, "Invalid value `{}` for {}!", value, name)
+            }
+            SetupError::Unsupported(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for SetupError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SetupError::Loading(error) => Some(error),
+            SetupError::Instance(error) => Some(error),
+            SetupError::Vulkan { result, .. } => Some(result),
+            SetupError::Io { source, .. } => Some(source),
+            SetupError::Image { source, .. } => Some(source),
+            SetupError::Obj { source, .. } => Some(source),
+            SetupError::Gltf { source, .. } => Some(source),
+            SetupError::Config { source, .. } => Some(source),
+            #[cfg(feature = "shader-compiler")]
+            SetupError::Watch { source, .. } => Some(source),
+            SetupError::Shader { .. }
+            | SetupError::ShaderCompilation { .. }
+            | SetupError::Argument { .. }
+            | SetupError::Unsupported(_) => None,
+        }
+    }
+}
+
+impl From<LoadingError> for SetupError {
+    fn from(error: LoadingError) -> Self {
+        SetupError::Loading(error)
+    }
+}
+
+impl From<InstanceError> for SetupError {
+    fn from(error: InstanceError) -> Self {
+        SetupError::Instance(error)
+    }
+}
+
+/// Attaches a description of the failed operation to raw Vulkan results.
+pub trait VkResultExt<T> {
+    fn context(self, context: &'static str) -> SetupResult<T>;
+}
+
+impl<T> VkResultExt<T> for VkResult<T> {
+    fn context(self, context: &'static str) -> SetupResult<T> {
+        self.map_err(|result| SetupError::Vulkan { context, result })
+    }
+}
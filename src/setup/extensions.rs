@@ -1,18 +1,34 @@
 use std::os::raw::c_char;
 
-use ash::extensions::{
-    ext::DebugUtils,
-    khr::{Surface, Swapchain, Win32Surface},
+use ash::{
+    extensions::{
+        ext::DebugUtils,
+        khr::{Surface, Swapchain},
+    },
+    Entry,
 };
 
-pub fn get_instance_extensions() -> Vec<*const c_char> {
-    vec![
-        Surface::name().as_ptr(),
-        Win32Surface::name().as_ptr(), // TODO replace with platform-specific getter
-        DebugUtils::name().as_ptr(),
-    ]
+use super::error::SetupResult;
+use super::platform::extensions as platform_extensions;
+
+pub fn get_instance_extensions(entry: &Entry, headless: bool) -> SetupResult<Vec<*const c_char>> {
+    let mut extensions = vec![];
+    if !headless {
+        extensions.push(Surface::name().as_ptr());
+        extensions.extend(
+            platform_extensions::get_surface_extensions(entry)?
+                .into_iter()
+                .map(|extension_name| extension_name.as_ptr()),
+        );
+    }
+    extensions.push(DebugUtils::name().as_ptr());
+    Ok(extensions)
 }
 
-pub fn get_device_extensions() -> Vec<*const c_char> {
-    vec![Swapchain::name().as_ptr()]
+pub fn get_device_extensions(headless: bool) -> Vec<*const c_char> {
+    if headless {
+        vec![]
+    } else {
+        vec![Swapchain::name().as_ptr()]
+    }
 }
@@ -4,13 +4,92 @@ use ash::{
     version::DeviceV1_0
 };
 
+use crate::setup::error::{SetupResult, VkResultExt};
+use crate::setup::queue::{Batch, Queue};
+
 pub struct FrameSyncData {
     pub image_available_semaphores: Vec<Semaphore>,
     pub render_finished_semaphores: Vec<Semaphore>,
-    pub in_flight_fences: Vec<Fence>
+    /// Empty when the graphics queue has a timeline, whose values frames wait on instead.
+    pub in_flight_fences: Vec<Fence>,
+    /// The timeline value the last submission of each frame slot signals.
+    pub frame_values: Vec<u64>,
+    /// The frame slot that last rendered to each swapchain image. Frames index these per-image
+    /// resources by the acquired image, which may still be in use by another frame when there
+    /// are more images than frames in flight or MAILBOX hands them out of order.
+    pub images_in_flight: Vec<Option<usize>>,
+}
+
+impl FrameSyncData {
+    /// Blocks until the last submission of `frame` has completed.
+    pub fn wait_for_frame(&self, device: &Device, queue: &Queue, frame: usize) -> SetupResult<()> {
+        match &queue.timeline {
+            Some(timeline) => timeline.wait(self.frame_values[frame]),
+            None => unsafe {
+                device
+                    .wait_for_fences(&[self.in_flight_fences[frame]], true, u64::MAX)
+                    .context("Failed to wait for fences")
+            },
+        }
+    }
+
+    /// Waits until no other frame renders to `image_index` and hands the image to `frame`.
+    pub fn acquire_image(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        image_index: usize,
+        frame: usize,
+    ) -> SetupResult<()> {
+        if let Some(image_frame) =
+            self.images_in_flight[image_index].filter(|image_frame| *image_frame != frame)
+        {
+            self.wait_for_frame(device, queue, image_frame)?;
+        }
+        self.images_in_flight[image_index] = Some(frame);
+        Ok(())
+    }
+
+    /// Submits the work of `frame`, signalling what `wait_for_frame` waits on; the frame's previous
+    /// submission must have completed.
+    pub fn submit(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        frame: usize,
+        batch: &Batch,
+    ) -> SetupResult<()> {
+        let fence = self
+            .in_flight_fences
+            .get(frame)
+            .copied()
+            .unwrap_or_else(Fence::null);
+        if fence != Fence::null() {
+            unsafe {
+                device
+                    .reset_fences(&[fence])
+                    .context("Failed to reset fences")?
+            };
+        }
+        if let Some(value) = queue.submit(device, batch, fence)? {
+            self.frame_values[frame] = value;
+        }
+        Ok(())
+    }
+
+    /// Forgets which frames used the images, for a new swapchain; call when the device is idle.
+    pub fn reset_images(&mut self, image_count: usize) {
+        self.images_in_flight = vec![None; image_count];
+    }
 }
 
-pub fn create(device: &Device, max_frames_in_flight: usize) -> FrameSyncData {
+/// Fences are only created when `queue` has no timeline.
+pub fn create(
+    device: &Device,
+    queue: &Queue,
+    max_frames_in_flight: usize,
+    image_count: usize,
+) -> SetupResult<FrameSyncData> {
     let mut image_available_semaphores = Vec::with_capacity(max_frames_in_flight);
     let mut render_finished_semaphores = Vec::with_capacity(max_frames_in_flight);
     let mut in_flight_fences = Vec::with_capacity(max_frames_in_flight);
@@ -20,21 +99,27 @@ pub fn create(device: &Device, max_frames_in_flight: usize) -> FrameSyncData {
         .flags(FenceCreateFlags::SIGNALED)
         .build();
 
-    (0..max_frames_in_flight).into_iter().for_each(|_| {
+    for _ in 0..max_frames_in_flight {
         unsafe {
-            let image_available_frame_semaphore = device.create_semaphore(&semaphore_create_info, None).expect("Failed to create image_available semaphore!");
-            let render_finished_frame_semaphore = device.create_semaphore(&semaphore_create_info, None).expect("Failed to create render_finished semaphore!");
-            let fence = device.create_fence(&fence_create_info, None).expect("Failed to create in-flight fence!");
+            let image_available_frame_semaphore = device.create_semaphore(&semaphore_create_info, None).context("Failed to create image_available semaphore")?;
+            let render_finished_frame_semaphore = device.create_semaphore(&semaphore_create_info, None).context("Failed to create render_finished semaphore")?;
             image_available_semaphores.push(image_available_frame_semaphore);
             render_finished_semaphores.push(render_finished_frame_semaphore);
-            in_flight_fences.push(fence);
+            if queue.timeline.is_none() {
+                let fence = device
+                    .create_fence(&fence_create_info, None)
+                    .context("Failed to create in-flight fence")?;
+                in_flight_fences.push(fence);
+            }
         }
-    });
+    }
 
 
-    FrameSyncData {
+    Ok(FrameSyncData {
         image_available_semaphores,
         render_finished_semaphores,
-        in_flight_fences
-    }
+        in_flight_fences,
+        frame_values: vec![0; max_frames_in_flight],
+        images_in_flight: vec![None; image_count],
+    })
 }
\ No newline at end of file
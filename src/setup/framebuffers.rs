@@ -1,32 +1,37 @@
 use ash::{version::DeviceV1_0, vk, Device};
 
-use crate::setup::swapchain::SwapchainData;
+use crate::setup::error::{SetupResult, VkResultExt};
+use crate::setup::render_target::RenderTarget;
 
+/// Without a multisampled color image (1x) the render target image is the color attachment.
 pub fn create(
     device: &Device,
-    swapchain_data: &SwapchainData,
+    render_target: &RenderTarget,
     render_pass: vk::RenderPass,
-    color_image_view: &vk::ImageView,
+    color_image_view: Option<&vk::ImageView>,
     depth_image_view: &vk::ImageView,
-) -> Vec<vk::Framebuffer> {
-    swapchain_data.swapchain_image_views.iter().fold(
-        Vec::with_capacity(swapchain_data.swapchain_image_views.len()),
-        |mut acc, image_view| {
-            let attachments = vec![*color_image_view, *depth_image_view, *image_view];
+) -> SetupResult<Vec<vk::Framebuffer>> {
+    let image_extent = render_target.image_extent();
+    render_target
+        .image_views()
+        .iter()
+        .map(|image_view| {
+            let attachments = match color_image_view {
+                Some(color_image_view) => vec![*color_image_view, *depth_image_view, *image_view],
+                None => vec![*image_view, *depth_image_view],
+            };
             let framebuffer_create_info = vk::FramebufferCreateInfo::builder()
                 .render_pass(render_pass)
                 .attachments(&attachments)
-                .width(swapchain_data.image_extent.width)
-                .height(swapchain_data.image_extent.height)
+                .width(image_extent.width)
+                .height(image_extent.height)
                 .layers(1);
 
-            let framebuffer = unsafe {
+            unsafe {
                 device
                     .create_framebuffer(&framebuffer_create_info, None)
-                    .expect("Failed to create framebuffer!")
-            };
-            acc.push(framebuffer);
-            acc
-        },
-    )
+                    .context("Failed to create framebuffer")
+            }
+        })
+        .collect()
 }
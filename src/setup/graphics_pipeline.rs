@@ -1,203 +1,522 @@
-use std::{ffi::CString, fs::File, io::Read, path::Path};
+use std::{
+    ffi::CString,
+    fs,
+    path::{Path, PathBuf},
+};
 
 extern crate byteorder;
 use byteorder::{ByteOrder, LittleEndian};
 
 use ash::{version::DeviceV1_0, vk, Device};
 
-use crate::setup::swapchain::SwapchainData;
+use crate::setup::error::{SetupError, SetupResult, VkResultExt};
+use crate::setup::shader_reflection::{self, ShaderInterface};
 use crate::structs::Vertex;
 
-pub fn create(
-    device: &Device,
-    swapchain_data: &SwapchainData,
-    render_pass: vk::RenderPass,
-    descriptor_set_layout: &vk::DescriptorSetLayout,
+/// The pipelines built for every render pass, stored in this order in `VulkanApp::pipelines`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PipelineKind {
+    Opaque,
+    /// Alpha blended, tested against the depth buffer without writing to it.
+    Transparent,
+    /// Triangle edges only; drawn filled on devices without `fillModeNonSolid`.
+    Wireframe,
+    /// Line lists, e.g. for debug geometry.
+    Lines,
+}
+
+impl PipelineKind {
+    pub const ALL: [PipelineKind; 4] = [
+        PipelineKind::Opaque,
+        PipelineKind::Transparent,
+        PipelineKind::Wireframe,
+        PipelineKind::Lines,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Color blend state presets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    Opaque,
+    /// `src * src_alpha + dst * (1 - src_alpha)`
+    Alpha,
+    /// `src * src_alpha + dst`
+    Additive,
+}
+
+impl BlendMode {
+    fn attachment_state(self) -> vk::PipelineColorBlendAttachmentState {
+        let builder = vk::PipelineColorBlendAttachmentState::builder().color_write_mask(
+            vk::ColorComponentFlags::R
+                | vk::ColorComponentFlags::G
+                | vk::ColorComponentFlags::B
+                | vk::ColorComponentFlags::A,
+        );
+        let dst_color_blend_factor = match self {
+            BlendMode::Opaque => return builder.blend_enable(false).build(),
+            BlendMode::Alpha => vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            BlendMode::Additive => vk::BlendFactor::ONE,
+        };
+        builder
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(dst_color_blend_factor)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .build()
+    }
+}
+
+/// The SPIR-V of one shader stage.
+#[derive(Clone, Debug)]
+pub enum ShaderCode {
+    /// A precompiled `.spv` file, read each time pipelines are built from it.
+    File(PathBuf),
+    /// Compiled at runtime from the source at `source`.
+    Compiled { source: PathBuf, words: Vec<u32> },
+}
+
+impl ShaderCode {
+    /// The file errors about this shader refer to.
+    pub fn path(&self) -> &Path {
+        match self {
+            ShaderCode::File(path) => path,
+            ShaderCode::Compiled { source, .. } => source,
+        }
+    }
+
+    fn load(&self) -> SetupResult<Vec<u32>> {
+        match self {
+            ShaderCode::File(path) => read_shader(path),
+            ShaderCode::Compiled { words, .. } => Ok(words.clone()),
+        }
+    }
+}
+
+/// Graphics pipeline state with the renderer's defaults: the `Vertex` layout, triangle lists,
+/// filled polygons, back-face culling with counter-clockwise front faces, depth test and write
+/// with LESS, and no blending. Viewport and scissor are always dynamic, so pipelines only depend
+/// on the render pass compatibility class (attachment formats and sample count).
+#[derive(Clone, Debug)]
+pub struct GraphicsPipelineBuilder {
+    shader_stages: Vec<(vk::ShaderStageFlags, ShaderCode)>,
+    vertex_bindings: Vec<vk::VertexInputBindingDescription>,
+    vertex_attributes: Vec<vk::VertexInputAttributeDescription>,
+    topology: vk::PrimitiveTopology,
+    polygon_mode: vk::PolygonMode,
+    cull_mode: vk::CullModeFlags,
+    front_face: vk::FrontFace,
+    depth_test: bool,
+    depth_write: bool,
+    depth_compare_op: vk::CompareOp,
+    blend_mode: BlendMode,
     msaa_samples: vk::SampleCountFlags,
-) -> (Vec<vk::Pipeline>, vk::PipelineLayout) {
-    let vert_shader_raw = read_shader(Path::new("src/shaders/vert.spv"));
-    let frag_shader_raw = read_shader(Path::new("src/shaders/frag.spv"));
+    min_sample_shading: Option<f32>,
+    push_constant_ranges: Vec<vk::PushConstantRange>,
+}
 
-    let vert_shader_module = create_shader_module(device, vert_shader_raw);
-    let frag_shader_module = create_shader_module(device, frag_shader_raw);
+impl Default for GraphicsPipelineBuilder {
+    fn default() -> Self {
+        Self {
+            shader_stages: vec![],
+            vertex_bindings: Vertex::get_binding_description(),
+            vertex_attributes: Vertex::get_attribute_descriptions(),
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon_mode: vk::PolygonMode::FILL,
+            cull_mode: vk::CullModeFlags::BACK,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            depth_test: true,
+            depth_write: true,
+            depth_compare_op: vk::CompareOp::LESS,
+            blend_mode: BlendMode::Opaque,
+            msaa_samples: vk::SampleCountFlags::TYPE_1,
+            min_sample_shading: None,
+            push_constant_ranges: vec![],
+        }
+    }
+}
 
-    // /!\ entry point (function) of shader; we're sticking to main functions.
-    let entry_point = CString::new("main").unwrap();
+impl GraphicsPipelineBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-    let vert_shader_stage_info = vk::PipelineShaderStageCreateInfo::builder()
-        .stage(vk::ShaderStageFlags::VERTEX)
-        .module(vert_shader_module)
-        .name(entry_point.as_c_str())
-        .build();
+    /// Adds a stage loaded from a SPIR-V file; its entry point has to be `main`.
+    pub fn shader_stage(self, stage: vk::ShaderStageFlags, path: &Path) -> Self {
+        self.shader_code(stage, ShaderCode::File(path.to_path_buf()))
+    }
 
-    let frag_shader_stage_info = vk::PipelineShaderStageCreateInfo::builder()
-        .stage(vk::ShaderStageFlags::FRAGMENT)
-        .module(frag_shader_module)
-        .name(entry_point.as_c_str())
-        .build();
+    /// Adds a stage from SPIR-V that may already be in memory; its entry point has to be `main`.
+    pub fn shader_code(mut self, stage: vk::ShaderStageFlags, code: ShaderCode) -> Self {
+        self.shader_stages.push((stage, code));
+        self
+    }
 
-    let shader_stages = vec![vert_shader_stage_info, frag_shader_stage_info];
+    pub fn vertex_layout(
+        mut self,
+        bindings: Vec<vk::VertexInputBindingDescription>,
+        attributes: Vec<vk::VertexInputAttributeDescription>,
+    ) -> Self {
+        self.vertex_bindings = bindings;
+        self.vertex_attributes = attributes;
+        self
+    }
 
-    let vertex_binding_descriptions = Vertex::get_binding_description();
-    let vertex_attribute_descriptions = Vertex::get_attribute_descriptions();
-    let pipeline_vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
-        .vertex_binding_descriptions(&vertex_binding_descriptions)
-        .vertex_attribute_descriptions(&vertex_attribute_descriptions)
-        .build();
+    pub fn topology(mut self, topology: vk::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
 
-    let pipeline_input_assembly_state_create_info =
-        vk::PipelineInputAssemblyStateCreateInfo::builder()
-            .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
-            .primitive_restart_enable(false)
+    /// Anything but FILL needs the `fillModeNonSolid` device feature.
+    pub fn polygon_mode(mut self, polygon_mode: vk::PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: vk::CullModeFlags, front_face: vk::FrontFace) -> Self {
+        self.cull_mode = cull_mode;
+        self.front_face = front_face;
+        self
+    }
+
+    pub fn depth(mut self, test: bool, write: bool, compare_op: vk::CompareOp) -> Self {
+        self.depth_test = test;
+        self.depth_write = write;
+        self.depth_compare_op = compare_op;
+        self
+    }
+
+    pub fn blend(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    /// Has to match the sample count of the render pass the pipeline is used with.
+    pub fn msaa_samples(mut self, msaa_samples: vk::SampleCountFlags) -> Self {
+        self.msaa_samples = msaa_samples;
+        self
+    }
+
+    /// Shades at least `min_sample_shading` of the samples individually; `None` shades once per
+    /// pixel. Needs the `sampleRateShading` device feature.
+    pub fn sample_shading(mut self, min_sample_shading: Option<f32>) -> Self {
+        self.min_sample_shading = min_sample_shading;
+        self
+    }
+
+    /// Push-constant ranges go into the pipeline layout, see `create_layout`.
+    pub fn push_constant_range(mut self, range: vk::PushConstantRange) -> Self {
+        self.push_constant_ranges.push(range);
+        self
+    }
+
+    /// A layout with the given descriptor set layouts and this builder's push-constant ranges;
+    /// pipelines sharing a layout have to agree on both.
+    pub fn create_layout(
+        &self,
+        device: &Device,
+        descriptor_set_layouts: &[vk::DescriptorSetLayout],
+    ) -> SetupResult<vk::PipelineLayout> {
+        let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(descriptor_set_layouts)
+            .push_constant_ranges(&self.push_constant_ranges)
             .build();
 
-    let viewports = [vk::Viewport::builder()
-        .x(0.0)
-        .y(0.0)
-        .width(swapchain_data.image_extent.width as f32)
-        .height(swapchain_data.image_extent.height as f32)
-        .min_depth(0.0)
-        .max_depth(1.0)
-        .build()];
-
-    let scissors = [vk::Rect2D::builder()
-        .offset(vk::Offset2D::builder().x(0).y(0).build())
-        .extent(swapchain_data.image_extent)
-        .build()];
-
-    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
-        .viewport_count(1)
-        .viewports(&viewports)
-        .scissor_count(1)
-        .scissors(&scissors)
-        .build();
+        unsafe {
+            device
+                .create_pipeline_layout(&pipeline_layout_create_info, None)
+                .context("Failed to create pipeline layout")
+        }
+    }
 
-    let rasterizer = vk::PipelineRasterizationStateCreateInfo::builder()
-        .depth_clamp_enable(false)
-        .rasterizer_discard_enable(false)
-        .polygon_mode(vk::PolygonMode::FILL)
-        .line_width(1.0)
-        .cull_mode(vk::CullModeFlags::BACK)
-        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
-        .depth_bias_enable(false)
-        .depth_bias_constant_factor(0.0)
-        .depth_bias_clamp(0.0)
-        .depth_bias_slope_factor(0.0)
-        .build();
+    /// Fails with `SetupError::Shader` when the vertex shader reads an input that the vertex
+    /// layout does not provide, or provides in a different format.
+    pub fn build(
+        &self,
+        device: &Device,
+        render_pass: vk::RenderPass,
+        pipeline_layout: vk::PipelineLayout,
+        pipeline_cache: vk::PipelineCache,
+    ) -> SetupResult<vk::Pipeline> {
+        let shader_modules = self.create_shader_modules(device)?;
+        let result = self.build_with_modules(
+            device,
+            render_pass,
+            pipeline_layout,
+            pipeline_cache,
+            &shader_modules,
+        );
 
-    let multisampling = vk::PipelineMultisampleStateCreateInfo::builder()
-        .sample_shading_enable(false)
-        .rasterization_samples(msaa_samples)
-        .min_sample_shading(1.0)
-        .alpha_to_coverage_enable(false)
-        .alpha_to_one_enable(false)
-        .build();
+        // modules are safe to destroy right after creating pipelines
+        destroy_shader_modules(device, &shader_modules);
+        result
+    }
 
-    let color_blend_attachments = [vk::PipelineColorBlendAttachmentState::builder()
-        .color_write_mask(
-            vk::ColorComponentFlags::R
-                | vk::ColorComponentFlags::G
-                | vk::ColorComponentFlags::B
-                | vk::ColorComponentFlags::A,
-        )
-        .blend_enable(true)
-        .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
-        .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
-        .color_blend_op(vk::BlendOp::ADD)
-        .src_alpha_blend_factor(vk::BlendFactor::ONE)
-        .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
-        .alpha_blend_op(vk::BlendOp::ADD)
-        .build()];
-
-    let color_blending = vk::PipelineColorBlendStateCreateInfo::builder()
-        .logic_op_enable(false)
-        .logic_op(vk::LogicOp::COPY)
-        .attachments(&color_blend_attachments)
-        .blend_constants([0.0, 0.0, 0.0, 0.0])
-        .build();
+    /// One module per stage, in `shader_stages` order; the vertex shader is checked against the
+    /// vertex layout first.
+    fn create_shader_modules(&self, device: &Device) -> SetupResult<Vec<vk::ShaderModule>> {
+        let mut shader_modules = Vec::with_capacity(self.shader_stages.len());
+        for (stage, code) in &self.shader_stages {
+            let shader_module = code.load().and_then(|shader_raw| {
+                if *stage == vk::ShaderStageFlags::VERTEX {
+                    let interface = shader_reflection::reflect(code.path(), &shader_raw)?;
+                    shader_reflection::check_vertex_inputs(
+                        code.path(),
+                        &interface,
+                        &self.vertex_attributes,
+                    )?;
+                }
+                create_shader_module(device, shader_raw)
+            });
+            match shader_module {
+                Ok(shader_module) => shader_modules.push(shader_module),
+                Err(error) => {
+                    destroy_shader_modules(device, &shader_modules);
+                    return Err(error);
+                }
+            }
+        }
+        Ok(shader_modules)
+    }
 
-    /*
-    let dynamic_states = [
-        vk::DynamicState::VIEWPORT,
-        vk::DynamicState::LINE_WIDTH
-    ];
+    fn build_with_modules(
+        &self,
+        device: &Device,
+        render_pass: vk::RenderPass,
+        pipeline_layout: vk::PipelineLayout,
+        pipeline_cache: vk::PipelineCache,
+        shader_modules: &[vk::ShaderModule],
+    ) -> SetupResult<vk::Pipeline> {
+        // /!\ entry point (function) of shader; we're sticking to main functions.
+        let entry_point = CString::new("main").unwrap();
 
-    let pipeline_dynamic_state_create_info = vk::PipelineDynamicStateCreateInfo::builder()
-        .dynamic_states(&dynamic_states)
-        .build();
-    */
+        let shader_stages = self
+            .shader_stages
+            .iter()
+            .zip(shader_modules)
+            .map(|((stage, _), module)| {
+                vk::PipelineShaderStageCreateInfo::builder()
+                    .stage(*stage)
+                    .module(*module)
+                    .name(entry_point.as_c_str())
+                    .build()
+            })
+            .collect::<Vec<vk::PipelineShaderStageCreateInfo>>();
 
-    let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
-        .set_layouts(&[*descriptor_set_layout])
-        .build();
+        let pipeline_vertex_input_state_create_info =
+            vk::PipelineVertexInputStateCreateInfo::builder()
+                .vertex_binding_descriptions(&self.vertex_bindings)
+                .vertex_attribute_descriptions(&self.vertex_attributes)
+                .build();
 
-    let pipeline_layout = unsafe {
-        device
-            .create_pipeline_layout(&pipeline_layout_create_info, None)
-            .expect("Failed to create pipeline layout!")
-    };
+        let pipeline_input_assembly_state_create_info =
+            vk::PipelineInputAssemblyStateCreateInfo::builder()
+                .topology(self.topology)
+                .primitive_restart_enable(false)
+                .build();
 
-    let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
-        .depth_test_enable(true)
-        .depth_write_enable(true)
-        .depth_compare_op(vk::CompareOp::LESS)
-        .depth_bounds_test_enable(false)
-        // .min_depth_bounds(0.0)
-        // .max_depth_bounds(1.0)
-        .stencil_test_enable(false)
-        //.front(vk::StencilOpState::builder().build())
-        //.back(vk::StencilOpState::builder().build())
-        .build();
+        // set when recording, see `command_buffers::record_draws`
+        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewport_count(1)
+            .scissor_count(1)
+            .build();
 
-    let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
-        .stages(&shader_stages)
-        .vertex_input_state(&pipeline_vertex_input_state_create_info)
-        .input_assembly_state(&pipeline_input_assembly_state_create_info)
-        .viewport_state(&viewport_state)
-        .rasterization_state(&rasterizer)
-        .multisample_state(&multisampling)
-        .color_blend_state(&color_blending)
-        .depth_stencil_state(&depth_stencil_state)
-        .layout(pipeline_layout)
-        .render_pass(render_pass)
-        .subpass(0)
-        .base_pipeline_handle(vk::Pipeline::default())
-        .base_pipeline_index(-1)
-        //.dynamic_state(&pipeline_dynamic_state_create_info)
-        .build();
+        let rasterizer = vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(self.polygon_mode)
+            .line_width(1.0)
+            .cull_mode(self.cull_mode)
+            .front_face(self.front_face)
+            .depth_bias_enable(false)
+            .depth_bias_constant_factor(0.0)
+            .depth_bias_clamp(0.0)
+            .depth_bias_slope_factor(0.0)
+            .build();
 
-    let pipelines = unsafe {
-        device
-            .create_graphics_pipelines(vk::PipelineCache::default(), &[pipeline_create_info], None)
-            .expect("Failed to create graphics pipeline!")
+        let multisampling = vk::PipelineMultisampleStateCreateInfo::builder()
+            .sample_shading_enable(self.min_sample_shading.is_some())
+            .rasterization_samples(self.msaa_samples)
+            .min_sample_shading(self.min_sample_shading.unwrap_or(1.0))
+            .alpha_to_coverage_enable(false)
+            .alpha_to_one_enable(false)
+            .build();
+
+        let color_blend_attachments = [self.blend_mode.attachment_state()];
+
+        let color_blending = vk::PipelineColorBlendStateCreateInfo::builder()
+            .logic_op_enable(false)
+            .logic_op(vk::LogicOp::COPY)
+            .attachments(&color_blend_attachments)
+            .blend_constants([0.0, 0.0, 0.0, 0.0])
+            .build();
+
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+
+        let pipeline_dynamic_state_create_info = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&dynamic_states)
+            .build();
+
+        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(self.depth_test)
+            .depth_write_enable(self.depth_write)
+            .depth_compare_op(self.depth_compare_op)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false)
+            .build();
+
+        let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&shader_stages)
+            .vertex_input_state(&pipeline_vertex_input_state_create_info)
+            .input_assembly_state(&pipeline_input_assembly_state_create_info)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterizer)
+            .multisample_state(&multisampling)
+            .color_blend_state(&color_blending)
+            .depth_stencil_state(&depth_stencil_state)
+            .layout(pipeline_layout)
+            .render_pass(render_pass)
+            .subpass(0)
+            .base_pipeline_handle(vk::Pipeline::default())
+            .base_pipeline_index(-1)
+            .dynamic_state(&pipeline_dynamic_state_create_info)
+            .build();
+
+        let pipelines = unsafe {
+            device
+                .create_graphics_pipelines(pipeline_cache, &[pipeline_create_info], None)
+                .map_err(|(_, result)| SetupError::Vulkan {
+                    context: "Failed to create graphics pipeline",
+                    result,
+                })?
+        };
+        Ok(pipelines[0])
+    }
+}
+
+/// The pipeline layout shared by every `PipelineKind`.
+pub fn create_layout(
+    device: &Device,
+    descriptor_set_layout: &vk::DescriptorSetLayout,
+    push_constant_ranges: &[vk::PushConstantRange],
+) -> SetupResult<vk::PipelineLayout> {
+    push_constant_ranges
+        .iter()
+        .fold(GraphicsPipelineBuilder::new(), |builder, range| {
+            builder.push_constant_range(*range)
+        })
+        .create_layout(device, &[*descriptor_set_layout])
+}
+
+/// Reads and reflects the shaders `create` builds pipelines from, vertex stage first.
+pub fn reflect_shaders(
+    vertex_shader: &ShaderCode,
+    fragment_shader: &ShaderCode,
+) -> SetupResult<Vec<ShaderInterface>> {
+    [vertex_shader, fragment_shader]
+        .iter()
+        .map(|code| shader_reflection::reflect(code.path(), &code.load()?))
+        .collect()
+}
+
+/// Builds one pipeline per `PipelineKind`, in `PipelineKind::ALL` order.
+#[allow(clippy::too_many_arguments)]
+pub fn create(
+    device: &Device,
+    render_pass: vk::RenderPass,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline_cache: vk::PipelineCache,
+    msaa_samples: vk::SampleCountFlags,
+    supported_features: &vk::PhysicalDeviceFeatures,
+    vertex_shader: &ShaderCode,
+    fragment_shader: &ShaderCode,
+) -> SetupResult<Vec<vk::Pipeline>> {
+    let base = GraphicsPipelineBuilder::new()
+        .shader_code(vk::ShaderStageFlags::VERTEX, vertex_shader.clone())
+        .shader_code(vk::ShaderStageFlags::FRAGMENT, fragment_shader.clone())
+        .msaa_samples(msaa_samples);
+    let wireframe_mode = if supported_features.fill_mode_non_solid == vk::TRUE {
+        vk::PolygonMode::LINE
+    } else {
+        vk::PolygonMode::FILL
     };
 
-    // modules are safe to destroy right after creating pipelines
-    unsafe {
-        device.destroy_shader_module(vert_shader_module, None);
-        device.destroy_shader_module(frag_shader_module, None);
+    // every kind shares the stages, so the shaders are read and checked once for all of them
+    let shader_modules = base.create_shader_modules(device)?;
+    let mut pipelines = Vec::with_capacity(PipelineKind::ALL.len());
+    for kind in PipelineKind::ALL.iter() {
+        let builder = match kind {
+            PipelineKind::Opaque => base.clone(),
+            PipelineKind::Transparent => base
+                .clone()
+                .depth(true, false, vk::CompareOp::LESS)
+                .blend(BlendMode::Alpha),
+            PipelineKind::Wireframe => base
+                .clone()
+                .polygon_mode(wireframe_mode)
+                .cull_mode(vk::CullModeFlags::NONE, vk::FrontFace::COUNTER_CLOCKWISE),
+            PipelineKind::Lines => base
+                .clone()
+                .topology(vk::PrimitiveTopology::LINE_LIST)
+                .cull_mode(vk::CullModeFlags::NONE, vk::FrontFace::COUNTER_CLOCKWISE),
+        };
+        let pipeline = builder.build_with_modules(
+            device,
+            render_pass,
+            pipeline_layout,
+            pipeline_cache,
+            &shader_modules,
+        );
+        match pipeline {
+            Ok(pipeline) => pipelines.push(pipeline),
+            Err(error) => {
+                pipelines
+                    .iter()
+                    .for_each(|pipeline| unsafe { device.destroy_pipeline(*pipeline, None) });
+                destroy_shader_modules(device, &shader_modules);
+                return Err(error);
+            }
+        }
     }
 
-    (pipelines, pipeline_layout)
+    destroy_shader_modules(device, &shader_modules);
+    Ok(pipelines)
 }
 
-fn read_shader(file_path: &Path) -> Vec<u32> {
-    let shader_file =
-        File::open(file_path).expect(&format!("Failed to read shader: {}", file_path.display()));
-    let shader_bytes = shader_file
-        .bytes()
-        .filter_map(|byte| byte.ok())
-        .collect::<Vec<u8>>();
-    let shader_raw: Vec<u32> = (0..shader_bytes.len())
-        .step_by(4)
-        .fold(vec![], |mut acc, i| {
-            acc.push(LittleEndian::read_u32(&shader_bytes[i..]));
-            acc
+fn destroy_shader_modules(device: &Device, shader_modules: &[vk::ShaderModule]) {
+    shader_modules
+        .iter()
+        .for_each(|module| unsafe { device.destroy_shader_module(*module, None) });
+}
+
+fn read_shader(file_path: &Path) -> SetupResult<Vec<u32>> {
+    let shader_bytes = fs::read(file_path).map_err(|source| SetupError::Io {
+        path: file_path.to_path_buf(),
+        source,
+    })?;
+    if shader_bytes.len() % 4 != 0 {
+        return Err(SetupError::Shader {
+            path: file_path.to_path_buf(),
+            message: format!(
+                "{} bytes is not a whole number of SPIR-V words",
+                shader_bytes.len()
+            ),
         });
-    shader_raw
+    }
+    let shader_raw: Vec<u32> = shader_bytes
+        .chunks_exact(4)
+        .map(LittleEndian::read_u32)
+        .collect();
+    Ok(shader_raw)
 }
 
-fn create_shader_module(device: &Device, shader_raw: Vec<u32>) -> vk::ShaderModule {
+fn create_shader_module(device: &Device, shader_raw: Vec<u32>) -> SetupResult<vk::ShaderModule> {
     let shader_module_create_info = vk::ShaderModuleCreateInfo::builder()
         .code(&shader_raw)
         .build();
@@ -205,7 +524,30 @@ fn create_shader_module(device: &Device, shader_raw: Vec<u32>) -> vk::ShaderModu
     let shader_module = unsafe {
         device
             .create_shader_module(&shader_module_create_info, None)
-            .expect("Failed to create shader module!")
+            .context("Failed to create shader module")?
     };
-    shader_module
+    Ok(shader_module)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn shaders_must_hold_whole_words() {
+        let path = env::temp_dir().join(format!("read-shader-test-{}.spv", std::process::id()));
+        fs::write(&path, [0x03, 0x02, 0x23, 0x07, 0x00]).unwrap();
+        let result = read_shader(&path);
+        fs::remove_file(&path).unwrap();
+        match result {
+            Err(SetupError::Shader { message, .. }) => assert!(message.contains("5 bytes")),
+            other => panic!("expected a shader error, got {:?}", other),
+        }
+
+        let bundled = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/shaders/vert.spv");
+        let words = read_shader(&bundled).unwrap();
+        assert_eq!(words[0], 0x0723_0203);
+    }
 }
@@ -1,83 +1,160 @@
-use std::ptr::copy_nonoverlapping;
+use std::{mem::size_of_val, path::Path, sync::Arc};
 
 use ash::{version::DeviceV1_0, vk, Device, Instance};
-use image::GenericImageView;
 
+use crate::setup::allocator::{Allocation, Allocator, ResourceKind};
 use crate::setup::buffer;
 use crate::setup::buffer::{begin_single_time_commands, end_single_time_commands};
+use crate::setup::error::{SetupError, SetupResult, VkResultExt};
+use crate::setup::queue::Queue;
 use ash::version::InstanceV1_0;
 
+/// An image with its bound sub-allocation and a view over all of its mip levels; all three are
+/// released when it is dropped.
+pub struct Image {
+    pub image: vk::Image,
+    pub allocation: Allocation,
+    pub view: vk::ImageView,
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+    pub mip_levels: u32,
+    allocator: Arc<Allocator>,
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        let device = self.allocator.device();
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+        }
+        if let Err(error) = self.allocator.free(&self.allocation) {
+            eprintln!("Warning: {}", error);
+        }
+    }
+}
+
+/// A sampled image together with its sampler.
+pub struct Texture {
+    pub image: Image,
+    pub sampler: vk::Sampler,
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            self.image
+                .allocator
+                .device()
+                .destroy_sampler(self.sampler, None)
+        };
+    }
+}
+
+/// Loads an image file of any color type into a mipmapped RGBA texture.
+#[allow(clippy::too_many_arguments)]
 pub fn create(
     instance: &Instance,
-    device: &Device,
+    allocator: &Arc<Allocator>,
     physical_device: &vk::PhysicalDevice,
     command_pool: vk::CommandPool,
-    queue: vk::Queue,
-) -> (vk::Image, vk::DeviceMemory, u32) {
-    let src = image::open("src/resources/textures/viking_room.png")
-        .expect("Failed to load image from path!");
+    queue: &Queue,
+    path: &Path,
+    format: vk::Format,
+    max_anisotropy: f32,
+) -> SetupResult<Texture> {
+    let src = image::open(path)
+        .map_err(|source| SetupError::Image {
+            path: path.to_path_buf(),
+            source,
+        })?
+        .into_rgba();
     let (width, height) = src.dimensions();
-    let mip_levels = f32::floor(f32::log2(u32::max(width, height) as f32)) as u32 + 1;
-    let image_size = (std::mem::size_of::<u8>() as u32 * width * height * 4) as vk::DeviceSize;
-    let src_bytes = src.to_bytes();
 
-    if image_size == 0 {
-        panic!("Failed to load texture image!")
+    if width == 0 || height == 0 {
+        return Err(SetupError::unsupported(format!(
+            "Texture image is empty: {}",
+            path.display()
+        )));
     }
 
-    let (staging_buffer, staging_memory) = buffer::create(
+    create_from_pixels(
         instance,
-        device,
+        allocator,
         physical_device,
+        command_pool,
+        queue,
+        width,
+        height,
+        &src.into_raw(),
+        format,
+        max_anisotropy,
+    )
+}
+
+/// Creates a mipmapped texture from tightly packed RGBA8 pixels.
+/// A `max_anisotropy` of 1 or less disables anisotropic filtering.
+#[allow(clippy::too_many_arguments)]
+pub fn create_from_pixels(
+    instance: &Instance,
+    allocator: &Arc<Allocator>,
+    physical_device: &vk::PhysicalDevice,
+    command_pool: vk::CommandPool,
+    queue: &Queue,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+    format: vk::Format,
+    max_anisotropy: f32,
+) -> SetupResult<Texture> {
+    let device = allocator.device();
+    let mip_levels = f32::floor(f32::log2(u32::max(width, height) as f32)) as u32 + 1;
+    let image_size = size_of_val(pixels) as vk::DeviceSize;
+
+    let staging_buffer = buffer::create(
+        allocator,
         image_size,
         vk::BufferUsageFlags::TRANSFER_SRC,
         vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-    );
+    )?;
 
-    unsafe {
-        let data_ptr = device
-            .map_memory(staging_memory, 0, image_size, vk::MemoryMapFlags::empty())
-            .expect("Failed to map texture buffer memory!");
-        copy_nonoverlapping(src_bytes.as_ptr(), data_ptr as *mut u8, src_bytes.len());
-        device.unmap_memory(staging_memory);
-    };
+    staging_buffer.write(pixels)?;
 
-    let (texture_image, texture_image_memory) = create_image(
-        instance,
-        device,
-        physical_device,
+    let texture_image = create_image(
+        allocator,
         width,
         height,
         mip_levels,
         vk::SampleCountFlags::TYPE_1,
-        vk::Format::R8G8B8A8_SRGB,
+        format,
         vk::ImageTiling::OPTIMAL,
         vk::ImageUsageFlags::TRANSFER_SRC
             | vk::ImageUsageFlags::TRANSFER_DST
             | vk::ImageUsageFlags::SAMPLED,
         vk::MemoryPropertyFlags::DEVICE_LOCAL,
-    );
+        vk::ImageAspectFlags::COLOR,
+    )?;
 
     transition_image_layout(
         device,
         command_pool,
         queue,
-        texture_image,
+        texture_image.image,
         // vk::Format::R8G8B8A8_SRGB,
         vk::ImageLayout::UNDEFINED,
         vk::ImageLayout::TRANSFER_DST_OPTIMAL,
         mip_levels,
-    );
+    )?;
 
     copy_buffer_to_image(
         device,
         command_pool,
         queue,
-        staging_buffer,
-        texture_image,
+        staging_buffer.buffer,
+        texture_image.image,
         width,
         height,
-    );
+    )?;
 
     // transition_image_layout(
     //     device,
@@ -96,33 +173,33 @@ pub fn create(
         physical_device,
         command_pool,
         queue,
-        texture_image,
-        vk::Format::R8G8B8A8_SRGB,
+        texture_image.image,
+        format,
         width,
         height,
         mip_levels,
-    );
+    )?;
 
-    unsafe {
-        device.destroy_buffer(staging_buffer, None);
-        device.free_memory(staging_memory, None);
-    }
-
-    (texture_image, texture_image_memory, mip_levels)
+    let sampler = create_texture_sampler(device, mip_levels, max_anisotropy)?;
+    Ok(Texture {
+        image: texture_image,
+        sampler,
+    })
 }
 
+#[allow(clippy::too_many_arguments)]
 fn generate_mipmaps(
     instance: &Instance,
     device: &Device,
     physical_device: &vk::PhysicalDevice,
     command_pool: vk::CommandPool,
-    queue: vk::Queue,
+    queue: &Queue,
     image: vk::Image,
     image_format: vk::Format,
     width: u32,
     height: u32,
     mip_levels: u32,
-) -> () {
+) -> SetupResult<()> {
     let format_properties =
         unsafe { instance.get_physical_device_format_properties(*physical_device, image_format) };
 
@@ -131,10 +208,12 @@ fn generate_mipmaps(
         .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR);
 
     if !supports_linear_filter_for_format {
-        panic!("Texture format does not support linear blitting!");
+        return Err(SetupError::unsupported(
+            "Texture format does not support linear blitting!",
+        ));
     }
 
-    let command_buffer = begin_single_time_commands(device, command_pool);
+    let command_buffer = begin_single_time_commands(device, command_pool)?;
 
     let mut mip_width = width;
     let mut mip_height = height;
@@ -291,13 +370,12 @@ fn generate_mipmaps(
         )
     }
 
-    end_single_time_commands(device, command_pool, command_buffer, queue);
+    end_single_time_commands(device, command_pool, command_buffer, queue)
 }
 
-fn create_image(
-    instance: &Instance,
-    device: &Device,
-    physical_device: &vk::PhysicalDevice,
+#[allow(clippy::too_many_arguments)]
+pub fn create_image(
+    allocator: &Arc<Allocator>,
     width: u32,
     height: u32,
     mip_levels: u32,
@@ -306,7 +384,9 @@ fn create_image(
     tiling: vk::ImageTiling,
     usage: vk::ImageUsageFlags,
     properties: vk::MemoryPropertyFlags,
-) -> (vk::Image, vk::DeviceMemory) {
+    aspect_flags: vk::ImageAspectFlags,
+) -> SetupResult<Image> {
+    let device = allocator.device();
     let image_create_info = vk::ImageCreateInfo::builder()
         .image_type(vk::ImageType::TYPE_2D)
         .extent(
@@ -326,64 +406,62 @@ fn create_image(
         .samples(samples)
         .build();
 
-    let image = unsafe {
+    let raw_image = unsafe {
         device
             .create_image(&image_create_info, None)
-            .expect("Failed to create image!")
+            .context("Failed to create image")?
     };
+    let memory_requirements = unsafe { device.get_image_memory_requirements(raw_image) };
 
-    let memory_requirements = unsafe { device.get_image_memory_requirements(image) };
-
-    let memory_type_index = buffer::find_memory_type_index(
-        instance,
-        physical_device,
-        memory_requirements.memory_type_bits,
-        properties,
-    )
-    .expect("Failed to find image memory type index!");
-
-    let memory_allocate_info = vk::MemoryAllocateInfo::builder()
-        .allocation_size(memory_requirements.size)
-        .memory_type_index(memory_type_index)
-        .build();
-
-    let texture_image_memory = unsafe {
-        device
-            .allocate_memory(&memory_allocate_info, None)
-            .expect("Failed to allocate image memory!")
+    let kind = if tiling == vk::ImageTiling::OPTIMAL {
+        ResourceKind::Optimal
+    } else {
+        ResourceKind::Linear
+    };
+    let allocation = match allocator.allocate(memory_requirements, properties, kind) {
+        Ok(allocation) => allocation,
+        Err(error) => {
+            unsafe { device.destroy_image(raw_image, None) };
+            return Err(error);
+        }
+    };
+    // from here on dropping the wrapper releases everything created so far on failure
+    let mut image = Image {
+        image: raw_image,
+        allocation,
+        view: vk::ImageView::null(),
+        format,
+        extent: vk::Extent2D { width, height },
+        mip_levels,
+        allocator: Arc::clone(allocator),
     };
 
     unsafe {
         device
-            .bind_image_memory(image, texture_image_memory, 0)
-            .expect("Failed to bind image memory!");
+            .bind_image_memory(
+                image.image,
+                image.allocation.memory,
+                image.allocation.offset,
+            )
+            .context("Failed to bind image memory")?;
     }
-    (image, texture_image_memory)
+    image.view = create_image_view(device, image.image, format, aspect_flags, mip_levels)?;
+    Ok(image)
 }
 
-pub fn create_texture_image_view(
+fn create_texture_sampler(
     device: &Device,
-    image: vk::Image,
     mip_levels: u32,
-) -> vk::ImageView {
-    create_image_view(
-        device,
-        image,
-        vk::Format::R8G8B8A8_SRGB,
-        vk::ImageAspectFlags::COLOR,
-        mip_levels,
-    )
-}
-
-pub fn create_texture_sampler(device: &Device, mip_levels: u32) -> vk::Sampler {
+    max_anisotropy: f32,
+) -> SetupResult<vk::Sampler> {
     let create_info = vk::SamplerCreateInfo::builder()
         .mag_filter(vk::Filter::LINEAR)
         .min_filter(vk::Filter::LINEAR)
         .address_mode_u(vk::SamplerAddressMode::REPEAT)
         .address_mode_v(vk::SamplerAddressMode::REPEAT)
         .address_mode_w(vk::SamplerAddressMode::REPEAT)
-        .anisotropy_enable(true)
-        .max_anisotropy(16.0)
+        .anisotropy_enable(max_anisotropy > 1.0)
+        .max_anisotropy(max_anisotropy.max(1.0))
         .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
         .unnormalized_coordinates(false)
         .compare_enable(false)
@@ -397,7 +475,7 @@ pub fn create_texture_sampler(device: &Device, mip_levels: u32) -> vk::Sampler {
     unsafe {
         device
             .create_sampler(&create_info, None)
-            .expect("Failed to create texture sampler!")
+            .context("Failed to create texture sampler")
     }
 }
 
@@ -411,14 +489,14 @@ struct LayoutTransitionMasks {
 fn transition_image_layout(
     device: &Device,
     command_pool: vk::CommandPool,
-    queue: vk::Queue,
+    queue: &Queue,
     image: vk::Image,
     // format: vk::Format,
     old_layout: vk::ImageLayout,
     new_layout: vk::ImageLayout,
     mip_levels: u32,
-) {
-    let command_buffer = buffer::begin_single_time_commands(device, command_pool);
+) -> SetupResult<()> {
+    let command_buffer = buffer::begin_single_time_commands(device, command_pool)?;
 
     let masks: LayoutTransitionMasks = match (old_layout, new_layout) {
         (vk::ImageLayout::UNDEFINED, vk::ImageLayout::TRANSFER_DST_OPTIMAL) => {
@@ -437,7 +515,12 @@ fn transition_image_layout(
                 dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
             }
         }
-        (_, _) => panic!("Unsupported layout transition!"),
+        (_, _) => {
+            return Err(SetupError::unsupported(format!(
+                "Unsupported layout transition from {:?} to {:?}!",
+                old_layout, new_layout
+            )))
+        }
     };
 
     let barrier = vk::ImageMemoryBarrier::builder()
@@ -471,19 +554,19 @@ fn transition_image_layout(
         )
     };
 
-    buffer::end_single_time_commands(device, command_pool, command_buffer, queue);
+    buffer::end_single_time_commands(device, command_pool, command_buffer, queue)
 }
 
 pub fn copy_buffer_to_image(
     device: &Device,
     command_pool: vk::CommandPool,
-    queue: vk::Queue,
+    queue: &Queue,
     buffer: vk::Buffer,
     image: vk::Image,
     width: u32,
     height: u32,
-) {
-    let command_buffer = buffer::begin_single_time_commands(device, command_pool);
+) -> SetupResult<()> {
+    let command_buffer = buffer::begin_single_time_commands(device, command_pool)?;
 
     let region = vk::BufferImageCopy::builder()
         .buffer_offset(0)
@@ -517,7 +600,7 @@ pub fn copy_buffer_to_image(
         );
     }
 
-    buffer::end_single_time_commands(device, command_pool, command_buffer, queue);
+    buffer::end_single_time_commands(device, command_pool, command_buffer, queue)
 }
 
 pub fn create_image_view(
@@ -526,7 +609,7 @@ pub fn create_image_view(
     format: vk::Format,
     aspect_flags: vk::ImageAspectFlags,
     mip_levels: u32,
-) -> vk::ImageView {
+) -> SetupResult<vk::ImageView> {
     let create_info = vk::ImageViewCreateInfo::builder()
         .image(image)
         .view_type(vk::ImageViewType::TYPE_2D)
@@ -545,22 +628,22 @@ pub fn create_image_view(
     unsafe {
         device
             .create_image_view(&create_info, None)
-            .expect("Failed to create image view!")
+            .context("Failed to create image view")
     }
 }
 
+/// `None` at 1x, where no resolve is needed and the render target is drawn to directly.
 pub fn create_color_resources(
-    instance: &Instance,
-    device: &Device,
-    physical_device: &vk::PhysicalDevice,
+    allocator: &Arc<Allocator>,
     swapchain_extent: vk::Extent2D,
     swapchain_image_format: vk::Format,
     msaa_samples: vk::SampleCountFlags,
-) -> (vk::Image, vk::ImageView, vk::DeviceMemory) {
-    let (color_image, color_image_memory) = create_image(
-        instance,
-        device,
-        physical_device,
+) -> SetupResult<Option<Image>> {
+    if msaa_samples == vk::SampleCountFlags::TYPE_1 {
+        return Ok(None);
+    }
+    create_image(
+        allocator,
         swapchain_extent.width,
         swapchain_extent.height,
         1,
@@ -569,29 +652,21 @@ pub fn create_color_resources(
         vk::ImageTiling::OPTIMAL,
         vk::ImageUsageFlags::TRANSIENT_ATTACHMENT | vk::ImageUsageFlags::COLOR_ATTACHMENT,
         vk::MemoryPropertyFlags::DEVICE_LOCAL,
-    );
-    let color_image_view = create_image_view(
-        device,
-        color_image,
-        swapchain_image_format,
         vk::ImageAspectFlags::COLOR,
-        1,
-    );
-    (color_image, color_image_view, color_image_memory)
+    )
+    .map(Some)
 }
 
 pub fn create_depth_resources(
     instance: &Instance,
-    device: &Device,
+    allocator: &Arc<Allocator>,
     physical_device: &vk::PhysicalDevice,
     swapchain_extent: vk::Extent2D,
     msaa_samples: vk::SampleCountFlags,
-) -> (vk::Image, vk::ImageView, vk::DeviceMemory) {
-    let depth_format = find_depth_format(instance, physical_device);
-    let (depth_image, depth_image_memory) = create_image(
-        instance,
-        device,
-        physical_device,
+) -> SetupResult<Image> {
+    let depth_format = find_depth_format(instance, physical_device)?;
+    create_image(
+        allocator,
         swapchain_extent.width,
         swapchain_extent.height,
         1,
@@ -600,18 +675,14 @@ pub fn create_depth_resources(
         vk::ImageTiling::OPTIMAL,
         vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
         vk::MemoryPropertyFlags::DEVICE_LOCAL,
-    );
-    let depth_image_view = create_image_view(
-        device,
-        depth_image,
-        depth_format,
         vk::ImageAspectFlags::DEPTH,
-        1,
-    );
-    (depth_image, depth_image_view, depth_image_memory)
+    )
 }
 
-pub fn find_depth_format(instance: &Instance, physical_device: &vk::PhysicalDevice) -> vk::Format {
+pub fn find_depth_format(
+    instance: &Instance,
+    physical_device: &vk::PhysicalDevice,
+) -> SetupResult<vk::Format> {
     find_supported_format(
         instance,
         physical_device,
@@ -631,7 +702,7 @@ pub fn find_supported_format(
     candidates: &[vk::Format],
     tiling: vk::ImageTiling,
     features: vk::FormatFeatureFlags,
-) -> vk::Format {
+) -> SetupResult<vk::Format> {
     for format in candidates.iter() {
         let format_properties =
             unsafe { instance.get_physical_device_format_properties(*physical_device, *format) };
@@ -643,10 +714,10 @@ pub fn find_supported_format(
             && (format_properties.optimal_tiling_features & features) == features;
 
         if linear_supported || optimal_supported {
-            return *format;
+            return Ok(*format);
         }
     }
-    panic!("Failed to find supported format!");
+    Err(SetupError::unsupported("Failed to find supported format!"))
 }
 
 // pub fn has_stencil_component(format: vk::Format) -> bool {
@@ -1,61 +1,41 @@
-use ash::{version::DeviceV1_0, vk, Device, Instance};
+use ash::vk;
 
-use std::{mem::size_of, ptr::copy_nonoverlapping};
+use std::{mem::size_of_val, sync::Arc};
 
-use crate::setup::buffer;
+use crate::setup::allocator::Allocator;
+use crate::setup::buffer::{self, Buffer};
+use crate::setup::error::SetupResult;
+use crate::setup::queue::Queue;
 
 pub fn create(
-    instance: &Instance,
-    physical_device: &vk::PhysicalDevice,
-    device: &Device,
+    allocator: &Arc<Allocator>,
     command_pool: vk::CommandPool,
-    queue: vk::Queue,
+    queue: &Queue,
     indices: &[u32],
-) -> (vk::Buffer, vk::DeviceMemory) {
-    let buffer_size = (size_of::<u32>() * indices.len()) as vk::DeviceSize;
-    let (staging_buffer, staging_buffer_memory) = buffer::create(
-        instance,
-        device,
-        physical_device,
+) -> SetupResult<Buffer> {
+    let buffer_size = size_of_val(indices) as vk::DeviceSize;
+    let staging_buffer = buffer::create(
+        allocator,
         buffer_size,
         vk::BufferUsageFlags::TRANSFER_SRC,
         vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-    );
+    )?;
+    staging_buffer.write(indices)?;
 
-    unsafe {
-        let data_ptr = device
-            .map_memory(
-                staging_buffer_memory,
-                0,
-                buffer_size,
-                vk::MemoryMapFlags::empty(),
-            )
-            .expect("Failed to map vertex buffer memory!");
-        copy_nonoverlapping(indices.as_ptr(), data_ptr as *mut u32, indices.len());
-        device.unmap_memory(staging_buffer_memory);
-    };
-
-    let (index_buffer, index_buffer_memory) = buffer::create(
-        instance,
-        device,
-        physical_device,
+    let index_buffer = buffer::create(
+        allocator,
         buffer_size,
         vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER,
         vk::MemoryPropertyFlags::DEVICE_LOCAL,
-    );
+    )?;
     buffer::copy(
-        device,
+        allocator.device(),
         command_pool,
         queue,
-        staging_buffer,
-        index_buffer,
+        staging_buffer.buffer,
+        index_buffer.buffer,
         buffer_size,
-    );
-
-    unsafe {
-        device.destroy_buffer(staging_buffer, None);
-        device.free_memory(staging_buffer_memory, None);
-    }
+    )?;
 
-    (index_buffer, index_buffer_memory)
+    Ok(index_buffer)
 }
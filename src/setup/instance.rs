@@ -5,28 +5,38 @@ use std::{
 
 use ash::{version::EntryV1_0, vk, Entry, Instance};
 
+use super::error::{SetupResult, VkResultExt};
 use super::extensions;
 use super::validation_layers::utils as debug_utils;
 
+/// Validation is left off when the layers are not installed; the returned flag tells whether
+/// they were enabled. The instance targets the newest Vulkan version up to 1.2 the loader
+/// supports, which is returned as well.
 pub fn create(
     enable_validation_layers: bool,
-) -> (Entry, Instance) {
-    let entry = Entry::new().expect("Failed to instantiate Vulkan entry!");
-    if enable_validation_layers && !debug_utils::check_validation_layer_support(&entry) {
-        panic!("Validation layers requested but not available!")
-    }
+    headless: bool,
+) -> SetupResult<(Entry, Instance, bool, u32)> {
+    let entry = Entry::new()?;
+    let enable_validation_layers =
+        enable_validation_layers && debug_utils::check_validation_layer_support(&entry)?;
 
     // Application Info
     let application_name = CString::new("Hello triangle").unwrap();
     let engine_name = CString::new("No engine").unwrap();
     let version = vk::make_version(1, 0, 0);
+    // 1.0 loaders cannot report their version
+    let api_version = entry
+        .try_enumerate_instance_version()
+        .context("Failed to query the instance version")?
+        .unwrap_or(version)
+        .min(vk::make_version(1, 2, 0));
 
     let application_info = vk::ApplicationInfo::builder()
         .application_name(&application_name)
         .application_version(version)
         .engine_name(&engine_name)
         .engine_version(version)
-        .api_version(version)
+        .api_version(api_version)
         .build();
 
     // Instance Create Info
@@ -36,7 +46,7 @@ pub fn create(
         .iter()
         .map(|layer_name| layer_name.as_ptr())
         .collect();
-    let enabled_extension_names = extensions::get_instance_extensions();
+    let enabled_extension_names = extensions::get_instance_extensions(&entry, headless)?;
     let mut debug_utils_messenger_create_info = debug_utils::populate_debug_messenger_create_info();
 
     let mut instance_create_info_builder =
@@ -53,6 +63,6 @@ pub fn create(
     let instance_create_info = instance_create_info_builder.build();
 
     // Instance creation
-    let instance = unsafe { entry.create_instance(&instance_create_info, None).expect("Failed to create Vulkan instance!") };
-    (entry, instance)
+    let instance = unsafe { entry.create_instance(&instance_create_info, None)? };
+    Ok((entry, instance, enable_validation_layers, api_version))
 }
@@ -0,0 +1,106 @@
+use std::{mem::size_of, sync::Arc};
+
+use ash::{vk, Instance};
+
+use crate::setup::allocator::Allocator;
+use crate::setup::buffer::{self, Buffer};
+use crate::setup::error::SetupResult;
+use crate::setup::image::{self, Texture};
+use crate::setup::queue::Queue;
+use crate::structs::{Material, MaterialFactors, TextureSource};
+
+const WHITE_PIXEL: [u8; 4] = [255, 255, 255, 255];
+/// A tangent-space normal pointing straight out of the surface.
+const FLAT_NORMAL_PIXEL: [u8; 4] = [128, 128, 255, 255];
+
+/// Textures and color factors of one material, bound together through one descriptor set.
+pub struct MaterialResources {
+    pub diffuse: Texture,
+    pub normal: Texture,
+    pub specular: Texture,
+    pub factors: Buffer,
+}
+
+pub fn create(
+    instance: &Instance,
+    allocator: &Arc<Allocator>,
+    physical_device: &vk::PhysicalDevice,
+    command_pool: vk::CommandPool,
+    queue: &Queue,
+    material: &Material,
+    max_anisotropy: f32,
+) -> SetupResult<MaterialResources> {
+    let create_texture =
+        |source: Option<&TextureSource>, format, fallback_pixel: [u8; 4]| match source {
+            Some(TextureSource::File(path)) => image::create(
+                instance,
+                allocator,
+                physical_device,
+                command_pool,
+                queue,
+                path,
+                format,
+                max_anisotropy,
+            ),
+            Some(TextureSource::Rgba {
+                width,
+                height,
+                pixels,
+            }) => image::create_from_pixels(
+                instance,
+                allocator,
+                physical_device,
+                command_pool,
+                queue,
+                *width,
+                *height,
+                pixels,
+                format,
+                max_anisotropy,
+            ),
+            None => image::create_from_pixels(
+                instance,
+                allocator,
+                physical_device,
+                command_pool,
+                queue,
+                1,
+                1,
+                &fallback_pixel,
+                format,
+                max_anisotropy,
+            ),
+        };
+
+    // only the diffuse map holds color; normal and specular maps are linear data
+    let diffuse = create_texture(
+        material.diffuse_texture.as_ref(),
+        vk::Format::R8G8B8A8_SRGB,
+        WHITE_PIXEL,
+    )?;
+    let normal = create_texture(
+        material.normal_texture.as_ref(),
+        vk::Format::R8G8B8A8_UNORM,
+        FLAT_NORMAL_PIXEL,
+    )?;
+    let specular = create_texture(
+        material.specular_texture.as_ref(),
+        vk::Format::R8G8B8A8_UNORM,
+        WHITE_PIXEL,
+    )?;
+
+    let factors = buffer::create(
+        allocator,
+        size_of::<MaterialFactors>() as vk::DeviceSize,
+        vk::BufferUsageFlags::UNIFORM_BUFFER,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    )?;
+    factors.write(&[MaterialFactors::from(material)])?;
+
+    Ok(MaterialResources {
+        diffuse,
+        normal,
+        specular,
+        factors,
+    })
+}
@@ -1,7 +1,11 @@
+pub mod allocator;
 pub mod buffer;
 pub mod command_buffers;
 pub mod command_pool;
+pub mod command_workers;
+pub mod context;
 pub mod devices;
+pub mod error;
 pub mod extensions;
 pub mod frame_sync;
 pub mod framebuffers;
@@ -9,9 +13,18 @@ pub mod graphics_pipeline;
 pub mod image;
 pub mod index_buffer;
 pub mod instance;
+pub mod material;
 pub mod model;
+pub mod offscreen;
+pub mod pipeline_cache;
 pub mod platform;
+pub mod queue;
+pub mod readback;
 pub mod render_pass;
+pub mod render_target;
+#[cfg(feature = "shader-compiler")]
+pub mod shader_compiler;
+pub mod shader_reflection;
 pub mod swapchain;
 pub mod uniform_buffers;
 pub mod validation_layers;
@@ -0,0 +1,375 @@
+use std::{path::Path, sync::Arc};
+
+use cgmath::{Matrix, Matrix3, Matrix4, SquareMatrix, Vector2, Vector3, Vector4, Zero};
+
+use crate::setup::error::{SetupError, SetupResult};
+use crate::setup::model::{self, NormalGeneration};
+use crate::structs::{Material, Mesh, TextureSource, Vertex};
+
+/// Loads every triangle primitive of the default scene, with node transforms baked into the
+/// vertices. External and embedded images are decoded up front.
+pub fn load(path: &Path, normal_generation: NormalGeneration) -> SetupResult<Mesh> {
+    let (document, buffers, images) = gltf::import(path).map_err(|source| SetupError::Gltf {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let images = images
+        .into_iter()
+        .enumerate()
+        .map(|(index, image)| convert_image(path, index, image))
+        .collect::<SetupResult<Vec<TextureSource>>>()?;
+
+    let materials = document
+        .materials()
+        .map(|material| convert_material(&material, &images))
+        .collect::<Vec<Material>>();
+    let default_material_id = materials.len();
+    let mut result = Mesh {
+        materials,
+        ..Mesh::default()
+    };
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| {
+            SetupError::unsupported(format!(
+                "Failed to load model {}: the file has no scene!",
+                path.display()
+            ))
+        })?;
+
+    let mut loader = SceneLoader {
+        path,
+        buffers: &buffers,
+        default_material_id,
+        normal_generation,
+        mesh: &mut result,
+    };
+    for node in scene.nodes() {
+        loader.load_node(&node, Matrix4::identity())?;
+    }
+    model::push_default_material(&mut result, default_material_id);
+
+    Ok(result)
+}
+
+struct SceneLoader<'a> {
+    path: &'a Path,
+    buffers: &'a [gltf::buffer::Data],
+    default_material_id: usize,
+    normal_generation: NormalGeneration,
+    mesh: &'a mut Mesh,
+}
+
+impl<'a> SceneLoader<'a> {
+    fn load_node(&mut self, node: &gltf::Node, parent_transform: Matrix4<f32>) -> SetupResult<()> {
+        let transform = parent_transform * Matrix4::from(node.transform().matrix());
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                let (corners, has_normals) = self.read_primitive(&primitive, transform)?;
+                let material_id = primitive
+                    .material()
+                    .index()
+                    .unwrap_or(self.default_material_id);
+                model::push_sub_mesh(
+                    self.mesh,
+                    corners,
+                    has_normals,
+                    material_id,
+                    self.normal_generation,
+                );
+            }
+        }
+
+        for child in node.children() {
+            self.load_node(&child, transform)?;
+        }
+        Ok(())
+    }
+
+    /// Expands the primitive to one vertex per triangle corner in model space.
+    fn read_primitive(
+        &self,
+        primitive: &gltf::Primitive,
+        transform: Matrix4<f32>,
+    ) -> SetupResult<(Vec<Vertex>, bool)> {
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            return Err(SetupError::unsupported(format!(
+                "Failed to load model {}: only triangle list primitives are supported!",
+                self.path.display()
+            )));
+        }
+
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let positions = reader
+            .read_positions()
+            .ok_or_else(|| {
+                SetupError::unsupported(format!(
+                    "Failed to load model {}: primitive without positions!",
+                    self.path.display()
+                ))
+            })?
+            .collect::<Vec<[f32; 3]>>();
+        let normals = reader
+            .read_normals()
+            .map(|normals| normals.collect::<Vec<[f32; 3]>>());
+        let tangents = reader
+            .read_tangents()
+            .map(|tangents| tangents.collect::<Vec<[f32; 4]>>());
+        let uvs = reader
+            .read_tex_coords(self.tex_coord_set(&primitive.material())?)
+            .map(|uvs| uvs.into_f32().collect::<Vec<[f32; 2]>>());
+        let colors = reader
+            .read_colors(0)
+            .map(|colors| colors.into_rgb_f32().collect::<Vec<[f32; 3]>>());
+        let mut indices = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect::<Vec<u32>>(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        check_primitive(
+            self.path,
+            positions.len(),
+            &[
+                ("normals", normals.as_ref().map(Vec::len)),
+                ("tangents", tangents.as_ref().map(Vec::len)),
+                ("texture coordinates", uvs.as_ref().map(Vec::len)),
+                ("colors", colors.as_ref().map(Vec::len)),
+            ],
+            &indices,
+        )?;
+
+        let linear = Matrix3::from_cols(
+            transform.x.truncate(),
+            transform.y.truncate(),
+            transform.z.truncate(),
+        );
+        let normal_matrix = linear.invert().unwrap_or(linear).transpose();
+        // mirroring transforms flip the winding order, which back-face culling depends on
+        if linear.determinant() < 0.0 {
+            indices.chunks_mut(3).for_each(|face| face.swap(1, 2));
+        }
+
+        let corners = indices
+            .iter()
+            .map(|&index| {
+                let index = index as usize;
+                let position = transform * Vector3::from(positions[index]).extend(1.0);
+                Vertex {
+                    position: position.truncate(),
+                    color: colors
+                        .as_ref()
+                        .map_or(Vector3::new(1.0, 1.0, 1.0), |colors| colors[index].into()),
+                    uv: uvs
+                        .as_ref()
+                        .map_or(Vector2::zero(), |uvs| uvs[index].into()),
+                    normal: normals.as_ref().map_or(Vector3::zero(), |normals| {
+                        model::normalize_or_zero(normal_matrix * Vector3::from(normals[index]))
+                    }),
+                    tangent: tangents.as_ref().map_or(Vector4::zero(), |tangents| {
+                        let [x, y, z, handedness] = tangents[index];
+                        model::normalize_or_zero(linear * Vector3::new(x, y, z)).extend(handedness)
+                    }),
+                }
+            })
+            .collect::<Vec<Vertex>>();
+
+        Ok((corners, normals.is_some()))
+    }
+
+    /// The UV set the material's textures are mapped with. Vertices carry a single one, so the
+    /// diffuse and normal map have to agree on it.
+    fn tex_coord_set(&self, material: &gltf::Material) -> SetupResult<u32> {
+        let diffuse_set = material
+            .pbr_metallic_roughness()
+            .base_color_texture()
+            .map(|info| info.tex_coord());
+        let normal_set = material.normal_texture().map(|normal| normal.tex_coord());
+        match (diffuse_set, normal_set) {
+            (Some(diffuse_set), Some(normal_set)) if diffuse_set != normal_set => {
+                Err(SetupError::unsupported(format!(
+                    "Failed to load model {}: material {} maps its diffuse and normal textures with different UV sets!",
+                    self.path.display(),
+                    material.name().unwrap_or("unnamed")
+                )))
+            }
+            (Some(set), _) | (None, Some(set)) => Ok(set),
+            (None, None) => Ok(0),
+        }
+    }
+}
+
+/// Every vertex attribute has to have one element per position, and the indices have to form
+/// whole triangles of existing vertices; the file format does not ensure either.
+fn check_primitive(
+    path: &Path,
+    position_count: usize,
+    attributes: &[(&str, Option<usize>)],
+    indices: &[u32],
+) -> SetupResult<()> {
+    let error = |message: String| {
+        Err(SetupError::unsupported(format!(
+            "Failed to load model {}: {}!",
+            path.display(),
+            message
+        )))
+    };
+    for &(name, len) in attributes {
+        match len {
+            Some(len) if len != position_count => {
+                return error(format!(
+                    "primitive has {} {} for {} positions",
+                    len, name, position_count
+                ))
+            }
+            _ => {}
+        }
+    }
+    if !indices.len().is_multiple_of(3) {
+        return error(format!(
+            "{} indices do not form whole triangles",
+            indices.len()
+        ));
+    }
+    if indices
+        .iter()
+        .any(|&index| index as usize >= position_count)
+    {
+        return error(String::from("vertex index out of range"));
+    }
+    Ok(())
+}
+
+/// Core glTF materials are metallic-roughness, so there is no specular map or color to read.
+fn convert_material(material: &gltf::Material, images: &[TextureSource]) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let texture_source = |texture: gltf::Texture| images.get(texture.source().index()).cloned();
+    let [r, g, b, _] = pbr.base_color_factor();
+
+    Material {
+        name: material.name().unwrap_or("unnamed").to_string(),
+        diffuse_color: Vector3::new(r, g, b),
+        diffuse_texture: pbr
+            .base_color_texture()
+            .and_then(|info| texture_source(info.texture())),
+        normal_texture: material
+            .normal_texture()
+            .and_then(|normal| texture_source(normal.texture())),
+        ..Material::default()
+    }
+}
+
+/// Expands the decoded image to RGBA8; 16-bit channels keep their most significant byte.
+fn convert_image(
+    path: &Path,
+    index: usize,
+    image: gltf::image::Data,
+) -> SetupResult<TextureSource> {
+    use gltf::image::Format;
+
+    let luma: fn(&[u8]) -> [u8; 4] = |pixel| [pixel[0], pixel[0], pixel[0], 255];
+    let luma_alpha: fn(&[u8]) -> [u8; 4] = |pixel| [pixel[0], pixel[0], pixel[0], pixel[1]];
+    let rgb: fn(&[u8]) -> [u8; 4] = |pixel| [pixel[0], pixel[1], pixel[2], 255];
+    let rgba: fn(&[u8]) -> [u8; 4] = |pixel| [pixel[0], pixel[1], pixel[2], pixel[3]];
+    let bgr: fn(&[u8]) -> [u8; 4] = |pixel| [pixel[2], pixel[1], pixel[0], 255];
+    let bgra: fn(&[u8]) -> [u8; 4] = |pixel| [pixel[2], pixel[1], pixel[0], pixel[3]];
+
+    let (channel_count, wide, expand) = match image.format {
+        Format::R8 => (1, false, luma),
+        Format::R8G8 => (2, false, luma_alpha),
+        Format::R8G8B8 => (3, false, rgb),
+        Format::R8G8B8A8 => (4, false, rgba),
+        Format::B8G8R8 => (3, false, bgr),
+        Format::B8G8R8A8 => (4, false, bgra),
+        Format::R16 => (1, true, luma),
+        Format::R16G16 => (2, true, luma_alpha),
+        Format::R16G16B16 => (3, true, rgb),
+        Format::R16G16B16A16 => (4, true, rgba),
+    };
+    let expected_len =
+        image.width as usize * image.height as usize * channel_count * if wide { 2 } else { 1 };
+    if image.pixels.len() != expected_len {
+        return Err(SetupError::unsupported(format!(
+            "Failed to load model {}: image {} holds {} bytes instead of {} for {}x{} {:?} pixels!",
+            path.display(),
+            index,
+            image.pixels.len(),
+            expected_len,
+            image.width,
+            image.height,
+            image.format
+        )));
+    }
+    let channels = if wide {
+        image
+            .pixels
+            .chunks_exact(2)
+            .map(|bytes| (u16::from_ne_bytes([bytes[0], bytes[1]]) >> 8) as u8)
+            .collect()
+    } else {
+        image.pixels
+    };
+
+    let pixels = channels
+        .chunks_exact(channel_count)
+        .flat_map(expand)
+        .collect::<Vec<u8>>();
+
+    Ok(TextureSource::Rgba {
+        width: image.width,
+        height: image.height,
+        pixels: Arc::new(pixels),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(attributes: &[(&str, Option<usize>)], indices: &[u32]) -> SetupResult<()> {
+        check_primitive(Path::new("model.gltf"), 4, attributes, indices)
+    }
+
+    fn error_message(result: SetupResult<()>) -> String {
+        match result {
+            Err(SetupError::Unsupported(message)) => message,
+            Err(error) => panic!("expected an unsupported model error, got {}", error),
+            Ok(()) => panic!("the primitive was accepted"),
+        }
+    }
+
+    #[test]
+    fn well_formed_primitives_are_accepted() {
+        let attributes = [("normals", Some(4)), ("colors", None)];
+        check(&attributes, &[0, 1, 2, 2, 3, 0]).unwrap();
+        check(&attributes, &[]).unwrap();
+    }
+
+    #[test]
+    fn short_attributes_are_rejected() {
+        let message = error_message(check(
+            &[("normals", Some(4)), ("texture coordinates", Some(3))],
+            &[0, 1, 2],
+        ));
+        assert!(
+            message.contains("3 texture coordinates for 4 positions"),
+            "{}",
+            message
+        );
+        assert!(check(&[("colors", Some(5))], &[0, 1, 2]).is_err());
+    }
+
+    #[test]
+    fn indices_must_form_whole_triangles() {
+        let message = error_message(check(&[], &[0, 1, 2, 3]));
+        assert!(message.contains("4 indices"), "{}", message);
+        assert!(check(&[], &[0, 1]).is_err());
+    }
+
+    #[test]
+    fn out_of_range_indices_are_rejected() {
+        let message = error_message(check(&[], &[0, 1, 4]));
+        assert!(message.contains("out of range"), "{}", message);
+    }
+}
@@ -0,0 +1,298 @@
+pub mod gltf_loader;
+pub mod obj_loader;
+
+use std::{collections::HashMap, ffi::OsStr, path::Path};
+
+use cgmath::{InnerSpace, Vector3, Zero};
+
+use crate::setup::error::{SetupError, SetupResult};
+use crate::structs::{Material, Mesh, SubMesh, Vertex};
+
+/// How normals are filled in for meshes whose file does not provide any.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalGeneration {
+    /// Every face gets its own normal; vertices shared between faces are split.
+    Flat,
+    /// Area-weighted average of the normals of every face sharing a position.
+    Smooth,
+}
+
+/// Loads an OBJ or glTF/GLB model, picking the loader by file extension.
+pub fn load(path: &Path, normal_generation: NormalGeneration) -> SetupResult<Mesh> {
+    let extension = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_ascii_lowercase);
+
+    let mesh = match extension.as_deref() {
+        Some("obj") => obj_loader::load(path, normal_generation),
+        Some("gltf") | Some("glb") => gltf_loader::load(path, normal_generation),
+        _ => Err(SetupError::unsupported(format!(
+            "Failed to load model {}: unsupported file extension!",
+            path.display()
+        ))),
+    }?;
+    // without sub-meshes there may be no material either, and nothing to allocate descriptors for
+    if mesh
+        .sub_meshes
+        .iter()
+        .all(|sub_mesh| sub_mesh.index_count == 0)
+    {
+        return Err(SetupError::unsupported(format!(
+            "Failed to load model {}: the file has no triangles!",
+            path.display()
+        )));
+    }
+    Ok(mesh)
+}
+
+/// Appends a sub-mesh given as triangle list corners: missing normals are generated and the
+/// corners are merged back into unique vertices.
+fn push_sub_mesh(
+    mesh: &mut Mesh,
+    corners: Vec<Vertex>,
+    has_normals: bool,
+    material_id: usize,
+    normal_generation: NormalGeneration,
+) {
+    let corners = if has_normals {
+        corners
+    } else {
+        generate_normals(corners, normal_generation)
+    };
+    let (vertices, indices) = deduplicate(&corners);
+
+    mesh.sub_meshes.push(SubMesh {
+        first_index: mesh.indices.len() as u32,
+        index_count: indices.len() as u32,
+        vertex_offset: mesh.vertices.len() as i32,
+        material_id,
+    });
+    mesh.vertices.extend(vertices);
+    mesh.indices.extend(indices);
+}
+
+/// Sub-meshes without a material of their own point one past the file's materials; this adds
+/// the default material there if any of them does.
+fn push_default_material(mesh: &mut Mesh, default_material_id: usize) {
+    if mesh
+        .sub_meshes
+        .iter()
+        .any(|sub_mesh| sub_mesh.material_id == default_material_id)
+    {
+        mesh.materials.push(Material::default());
+    }
+}
+
+fn generate_normals(mut corners: Vec<Vertex>, normal_generation: NormalGeneration) -> Vec<Vertex> {
+    // the cross product's length is twice the face area, which weights the smooth average
+    let face_normals = corners
+        .chunks(3)
+        .map(|face| {
+            (face[1].position - face[0].position).cross(face[2].position - face[0].position)
+        })
+        .collect::<Vec<Vector3<f32>>>();
+
+    match normal_generation {
+        NormalGeneration::Flat => {
+            for (face, face_normal) in corners.chunks_mut(3).zip(face_normals) {
+                let normal = normalize_or_zero(face_normal);
+                face.iter_mut().for_each(|vertex| vertex.normal = normal);
+            }
+        }
+        NormalGeneration::Smooth => {
+            // keyed by position rather than file index, so UV seams do not show up as hard edges
+            let mut position_normals: HashMap<[u32; 3], Vector3<f32>> = HashMap::new();
+            for (face, face_normal) in corners.chunks(3).zip(face_normals) {
+                for vertex in face {
+                    *position_normals
+                        .entry(position_key(&vertex.position))
+                        .or_insert_with(Vector3::zero) += face_normal;
+                }
+            }
+            for vertex in corners.iter_mut() {
+                vertex.normal =
+                    normalize_or_zero(position_normals[&position_key(&vertex.position)]);
+            }
+        }
+    }
+    corners
+}
+
+fn deduplicate(corners: &[Vertex]) -> (Vec<Vertex>, Vec<u32>) {
+    let mut unique_vertices = HashMap::new();
+    let mut vertices = vec![];
+    let mut indices = Vec::with_capacity(corners.len());
+
+    for vertex in corners {
+        let index = *unique_vertices.entry(vertex.hash_key()).or_insert_with(|| {
+            vertices.push(*vertex);
+            vertices.len() as u32 - 1
+        });
+        indices.push(index);
+    }
+    (vertices, indices)
+}
+
+fn position_key(position: &Vector3<f32>) -> [u32; 3] {
+    [
+        position.x.to_bits(),
+        position.y.to_bits(),
+        position.z.to_bits(),
+    ]
+}
+
+fn normalize_or_zero(vector: Vector3<f32>) -> Vector3<f32> {
+    if vector.magnitude2() > 0.0 {
+        vector.normalize()
+    } else {
+        vector
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+        Vertex::new(x, y, z, 1.0, 1.0, 1.0, 0.0, 0.0)
+    }
+
+    /// Two triangles of a unit square in the z = 0 plane, sharing the diagonal.
+    fn square() -> Vec<Vertex> {
+        vec![
+            vertex(0.0, 0.0, 0.0),
+            vertex(1.0, 0.0, 0.0),
+            vertex(1.0, 1.0, 0.0),
+            vertex(0.0, 0.0, 0.0),
+            vertex(1.0, 1.0, 0.0),
+            vertex(0.0, 1.0, 0.0),
+        ]
+    }
+
+    /// Two triangles folded along the y axis: one in the z = 0 plane, one in the x = 0 plane.
+    fn fold() -> Vec<Vertex> {
+        vec![
+            vertex(0.0, 0.0, 0.0),
+            vertex(1.0, 0.0, 0.0),
+            vertex(0.0, 1.0, 0.0),
+            vertex(0.0, 0.0, 0.0),
+            vertex(0.0, 1.0, 0.0),
+            vertex(0.0, 0.0, 1.0),
+        ]
+    }
+
+    fn assert_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(
+            (actual - expected).magnitude() < 1e-6,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn deduplicate_merges_identical_vertices() {
+        let (vertices, indices) = deduplicate(&square());
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices, vec![0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn deduplicate_keeps_vertices_differing_in_any_attribute() {
+        let mut corners = vec![vertex(0.0, 0.0, 0.0); 5];
+        corners[1].uv.x = 1.0;
+        corners[2].normal = Vector3::unit_x();
+        corners[3].tangent.w = -1.0;
+        corners[4].color.z = 0.5;
+        let (vertices, indices) = deduplicate(&corners);
+        assert_eq!(vertices.len(), 5);
+        assert_eq!(indices, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn hash_key_distinguishes_signed_zeros() {
+        // the bit patterns differ, so -0.0 is kept apart even though it compares equal to 0.0
+        assert_ne!(
+            vertex(0.0, 0.0, 0.0).hash_key(),
+            vertex(-0.0, 0.0, 0.0).hash_key()
+        );
+        assert_eq!(
+            vertex(1.0, 2.0, 3.0).hash_key(),
+            vertex(1.0, 2.0, 3.0).hash_key()
+        );
+    }
+
+    #[test]
+    fn flat_normals_follow_the_winding_of_each_face() {
+        let corners = generate_normals(fold(), NormalGeneration::Flat);
+        corners[..3]
+            .iter()
+            .for_each(|vertex| assert_close(vertex.normal, Vector3::unit_z()));
+        corners[3..]
+            .iter()
+            .for_each(|vertex| assert_close(vertex.normal, Vector3::unit_x()));
+
+        // the shared edge is split into one vertex per face
+        let (vertices, _) = deduplicate(&corners);
+        assert_eq!(vertices.len(), 6);
+    }
+
+    #[test]
+    fn smooth_normals_average_the_faces_sharing_a_position() {
+        let corners = generate_normals(fold(), NormalGeneration::Smooth);
+        let diagonal = Vector3::new(1.0, 0.0, 1.0).normalize();
+        for vertex in &corners {
+            let expected = if vertex.position.x == 0.0 && vertex.position.z == 0.0 {
+                diagonal
+            } else if vertex.position.x > 0.0 {
+                Vector3::unit_z()
+            } else {
+                Vector3::unit_x()
+            };
+            assert_close(vertex.normal, expected);
+        }
+
+        let (vertices, _) = deduplicate(&corners);
+        assert_eq!(vertices.len(), 4);
+    }
+
+    #[test]
+    fn smooth_normals_weight_faces_by_area() {
+        let mut corners = fold();
+        // stretch the second face to four times the area of the first
+        corners[4].position.y = 2.0;
+        corners[5].position.z = 2.0;
+        let corners = generate_normals(corners, NormalGeneration::Smooth);
+        assert_close(corners[0].normal, Vector3::new(4.0, 0.0, 1.0).normalize());
+    }
+
+    #[test]
+    fn degenerate_faces_get_zero_normals() {
+        let corners = vec![
+            vertex(0.0, 0.0, 0.0),
+            vertex(1.0, 0.0, 0.0),
+            vertex(2.0, 0.0, 0.0),
+        ];
+        for normal_generation in [NormalGeneration::Flat, NormalGeneration::Smooth] {
+            generate_normals(corners.clone(), normal_generation)
+                .iter()
+                .for_each(|vertex| assert_eq!(vertex.normal, Vector3::zero()));
+        }
+    }
+
+    #[test]
+    fn models_without_triangles_are_rejected() {
+        let path = std::env::temp_dir().join(format!("empty-model-{}.obj", std::process::id()));
+        std::fs::write(&path, "v 0 0 0\nv 1 0 0\n").unwrap();
+        let result = load(&path, NormalGeneration::Smooth);
+        std::fs::remove_file(&path).unwrap();
+        match result {
+            Err(SetupError::Unsupported(message)) => {
+                assert!(message.contains("no triangles"), "{}", message)
+            }
+            Err(error) => panic!("expected an unsupported model error, got {}", error),
+            Ok(_) => panic!("a model without triangles was loaded"),
+        }
+    }
+}
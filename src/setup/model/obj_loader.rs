@@ -0,0 +1,142 @@
+use crate::setup::error::{SetupError, SetupResult};
+use crate::setup::model::{self, NormalGeneration};
+use crate::structs::{Material, Mesh, TextureSource, Vertex};
+
+use std::{collections::HashMap, fs, path::Path};
+
+extern crate tobj;
+use cgmath::{Vector2, Vector3, Vector4, Zero};
+
+pub fn load(path: &Path, normal_generation: NormalGeneration) -> SetupResult<Mesh> {
+    let mut result = Mesh::default();
+
+    let (models, materials) = tobj::load_obj(path, true).map_err(|source| SetupError::Obj {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let vertex_colors = read_vertex_colors(path)?;
+
+    // MTL texture paths are relative to the OBJ file
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    result.materials = materials
+        .iter()
+        .map(|material| convert_material(material, base_dir))
+        .collect();
+    let default_material_id = result.materials.len();
+
+    for model in models.iter() {
+        let tobj::Model { mesh, name: _name } = model;
+        let corners = mesh
+            .indices
+            .iter()
+            .map(|&index| read_vertex(mesh, index as usize, &vertex_colors))
+            .collect::<Vec<Vertex>>();
+
+        model::push_sub_mesh(
+            &mut result,
+            corners,
+            !mesh.normals.is_empty(),
+            mesh.material_id.unwrap_or(default_material_id),
+            normal_generation,
+        );
+    }
+    model::push_default_material(&mut result, default_material_id);
+
+    Ok(result)
+}
+
+fn convert_material(material: &tobj::Material, base_dir: &Path) -> Material {
+    let texture_source = |texture: &str| -> Option<TextureSource> {
+        if texture.is_empty() {
+            None
+        } else {
+            Some(TextureSource::File(base_dir.join(texture)))
+        }
+    };
+
+    Material {
+        name: material.name.clone(),
+        diffuse_color: material.diffuse.into(),
+        specular_color: material.specular.into(),
+        shininess: material.shininess,
+        diffuse_texture: texture_source(&material.diffuse_texture),
+        normal_texture: texture_source(&material.normal_texture),
+        specular_texture: texture_source(&material.specular_texture),
+    }
+}
+
+fn read_vertex(
+    mesh: &tobj::Mesh,
+    index: usize,
+    vertex_colors: &HashMap<[u32; 3], Vector3<f32>>,
+) -> Vertex {
+    let position = Vector3 {
+        x: mesh.positions[index * 3],
+        y: mesh.positions[index * 3 + 1],
+        z: mesh.positions[index * 3 + 2],
+    };
+    let color = vertex_colors
+        .get(&model::position_key(&position))
+        .copied()
+        .unwrap_or(Vector3 {
+            x: 1.0,
+            y: 1.0,
+            z: 1.0,
+        });
+    let uv = if mesh.texcoords.is_empty() {
+        Vector2::zero()
+    } else {
+        Vector2 {
+            x: mesh.texcoords[index * 2],
+            y: 1.0 - mesh.texcoords[index * 2 + 1],
+        }
+    };
+    let normal = if mesh.normals.is_empty() {
+        Vector3::zero()
+    } else {
+        Vector3 {
+            x: mesh.normals[index * 3],
+            y: mesh.normals[index * 3 + 1],
+            z: mesh.normals[index * 3 + 2],
+        }
+    };
+
+    Vertex {
+        position,
+        color,
+        uv,
+        normal,
+        // OBJ has no tangents
+        tangent: Vector4::zero(),
+    }
+}
+
+/// tobj drops the optional `r g b` that may trail a `v` line, so colors are read separately and
+/// matched back to the loaded vertices by position.
+fn read_vertex_colors(path: &Path) -> SetupResult<HashMap<[u32; 3], Vector3<f32>>> {
+    let source = fs::read_to_string(path).map_err(|source| SetupError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let vertex_colors = source
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            if words.next() != Some("v") {
+                return None;
+            }
+            let values = words
+                .map(str::parse::<f32>)
+                .collect::<Result<Vec<f32>, _>>()
+                .ok()?;
+            if values.len() < 6 {
+                return None;
+            }
+            let position = Vector3::new(values[0], values[1], values[2]);
+            let color = Vector3::new(values[3], values[4], values[5]);
+            Some((model::position_key(&position), color))
+        })
+        .collect();
+    Ok(vertex_colors)
+}
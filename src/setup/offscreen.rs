@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use ash::{vk, Instance};
+
+use crate::setup::{
+    allocator::Allocator,
+    error::SetupResult,
+    image::{self, Image},
+};
+
+/// Offscreen counterpart of `SwapchainData` used for headless rendering; the resolved color
+/// attachments are plain images that are never presented.
+pub struct OffscreenData {
+    pub images: Vec<Image>,
+    pub image_format: vk::Format,
+    pub image_extent: vk::Extent2D,
+}
+
+impl OffscreenData {
+    pub fn new(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        allocator: &Arc<Allocator>,
+        image_extent: vk::Extent2D,
+        image_count: usize,
+    ) -> SetupResult<Self> {
+        // prefer the format a desktop swapchain would pick so both paths render identically
+        let image_format = image::find_supported_format(
+            instance,
+            &physical_device,
+            &[vk::Format::B8G8R8A8_SRGB, vk::Format::R8G8B8A8_SRGB],
+            vk::ImageTiling::OPTIMAL,
+            vk::FormatFeatureFlags::COLOR_ATTACHMENT,
+        )?;
+
+        let images = (0..image_count)
+            .map(|_| {
+                image::create_image(
+                    allocator,
+                    image_extent.width,
+                    image_extent.height,
+                    1,
+                    vk::SampleCountFlags::TYPE_1,
+                    image_format,
+                    vk::ImageTiling::OPTIMAL,
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    vk::ImageAspectFlags::COLOR,
+                )
+            })
+            .collect::<SetupResult<Vec<Image>>>()?;
+
+        Ok(OffscreenData {
+            images,
+            image_format,
+            image_extent,
+        })
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use ash::{
+    version::{DeviceV1_0, InstanceV1_0},
+    vk, Device, Instance,
+};
+use byteorder::{ByteOrder, NativeEndian};
+
+use crate::setup::error::{SetupError, SetupResult, VkResultExt};
+
+/// Size of `VkPipelineCacheHeaderVersionOne`: header size, header version, vendor ID, device ID
+/// and the pipeline cache UUID.
+const HEADER_SIZE: usize = 16 + vk::UUID_SIZE;
+
+/// A pipeline cache shared by every pipeline creation call, optionally backed by a file so
+/// compiled pipelines survive restarts. The handle is destroyed on drop; saving is explicit.
+pub struct PipelineCache {
+    pub cache: vk::PipelineCache,
+    path: Option<PathBuf>,
+    device: Arc<Device>,
+}
+
+impl PipelineCache {
+    /// Seeds the cache from `path` when the file exists and was written by the same driver and
+    /// device; anything else (missing, unreadable or stale data) starts an empty cache.
+    pub fn load(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &Arc<Device>,
+        path: Option<&Path>,
+    ) -> SetupResult<Self> {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let initial_data = path
+            .and_then(|path| fs::read(path).ok())
+            .filter(|data| is_compatible(data, &properties))
+            .unwrap_or_default();
+
+        let create_info = vk::PipelineCacheCreateInfo::builder()
+            .initial_data(&initial_data)
+            .build();
+        let cache = unsafe {
+            device
+                .create_pipeline_cache(&create_info, None)
+                .context("Failed to create pipeline cache")?
+        };
+
+        Ok(Self {
+            cache,
+            path: path.map(Path::to_path_buf),
+            device: Arc::clone(device),
+        })
+    }
+
+    /// Writes the cache contents back to its file; does nothing for a cache without one.
+    pub fn save(&self) -> SetupResult<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let data = unsafe {
+            self.device
+                .get_pipeline_cache_data(self.cache)
+                .context("Failed to get pipeline cache data")?
+        };
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent).map_err(|source| SetupError::Io {
+                path: parent.to_path_buf(),
+                source,
+            })?;
+        }
+        fs::write(path, data).map_err(|source| SetupError::Io {
+            path: path.clone(),
+            source,
+        })
+    }
+}
+
+impl Drop for PipelineCache {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_pipeline_cache(self.cache, None) };
+    }
+}
+
+/// Drivers are expected to reject foreign data themselves, but some crash on it instead, so the
+/// header is checked against the device before the data is handed over.
+fn is_compatible(data: &[u8], properties: &vk::PhysicalDeviceProperties) -> bool {
+    if data.len() < HEADER_SIZE {
+        return false;
+    }
+    let header_size = NativeEndian::read_u32(&data[0..4]) as usize;
+    let header_version = NativeEndian::read_i32(&data[4..8]);
+    let vendor_id = NativeEndian::read_u32(&data[8..12]);
+    let device_id = NativeEndian::read_u32(&data[12..16]);
+    let uuid = &data[16..HEADER_SIZE];
+
+    header_size >= HEADER_SIZE
+        && header_size <= data.len()
+        && header_version == vk::PipelineCacheHeaderVersion::ONE.as_raw()
+        && vendor_id == properties.vendor_id
+        && device_id == properties.device_id
+        && uuid == properties.pipeline_cache_uuid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties() -> vk::PhysicalDeviceProperties {
+        vk::PhysicalDeviceProperties {
+            vendor_id: 0x10de,
+            device_id: 0x1f08,
+            pipeline_cache_uuid: [7; vk::UUID_SIZE],
+            ..vk::PhysicalDeviceProperties::default()
+        }
+    }
+
+    /// A header as the device of `properties()` writes it, followed by `payload_len` bytes.
+    fn cache_data(payload_len: usize) -> Vec<u8> {
+        let properties = properties();
+        let mut data = vec![0; HEADER_SIZE + payload_len];
+        NativeEndian::write_u32(&mut data[0..4], HEADER_SIZE as u32);
+        NativeEndian::write_i32(
+            &mut data[4..8],
+            vk::PipelineCacheHeaderVersion::ONE.as_raw(),
+        );
+        NativeEndian::write_u32(&mut data[8..12], properties.vendor_id);
+        NativeEndian::write_u32(&mut data[12..16], properties.device_id);
+        data[16..HEADER_SIZE].copy_from_slice(&properties.pipeline_cache_uuid);
+        data
+    }
+
+    #[test]
+    fn data_from_the_same_device_is_accepted() {
+        assert!(is_compatible(&cache_data(0), &properties()));
+        assert!(is_compatible(&cache_data(64), &properties()));
+    }
+
+    #[test]
+    fn truncated_data_is_rejected() {
+        let data = cache_data(0);
+        assert!(!is_compatible(&[], &properties()));
+        assert!(!is_compatible(&data[..HEADER_SIZE - 1], &properties()));
+
+        // the header claims more bytes than the file holds
+        let mut data = cache_data(8);
+        NativeEndian::write_u32(&mut data[0..4], HEADER_SIZE as u32 + 16);
+        assert!(!is_compatible(&data, &properties()));
+    }
+
+    #[test]
+    fn header_size_and_version_are_checked() {
+        let mut data = cache_data(8);
+        NativeEndian::write_u32(&mut data[0..4], HEADER_SIZE as u32 - 1);
+        assert!(!is_compatible(&data, &properties()));
+
+        let mut data = cache_data(8);
+        NativeEndian::write_i32(&mut data[4..8], 2);
+        assert!(!is_compatible(&data, &properties()));
+    }
+
+    #[test]
+    fn data_from_another_device_is_rejected() {
+        let other_vendor = vk::PhysicalDeviceProperties {
+            vendor_id: 0x1002,
+            ..properties()
+        };
+        let other_device = vk::PhysicalDeviceProperties {
+            device_id: 0x1f09,
+            ..properties()
+        };
+        let mut other_uuid = properties();
+        other_uuid.pipeline_cache_uuid[vk::UUID_SIZE - 1] = 8;
+
+        let data = cache_data(8);
+        assert!(!is_compatible(&data, &other_vendor));
+        assert!(!is_compatible(&data, &other_device));
+        assert!(!is_compatible(&data, &other_uuid));
+    }
+}
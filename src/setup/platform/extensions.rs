@@ -0,0 +1,51 @@
+use std::ffi::CStr;
+
+use ash::{version::EntryV1_0, Entry};
+
+use crate::setup::error::{SetupError, SetupResult, VkResultExt};
+
+#[cfg(target_os = "windows")]
+use ash::extensions::khr::Win32Surface;
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+use ash::extensions::khr::{WaylandSurface, XcbSurface, XlibSurface};
+
+#[cfg(target_os = "windows")]
+fn get_candidate_surface_extensions() -> Vec<&'static CStr> {
+    vec![Win32Surface::name()]
+}
+
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+fn get_candidate_surface_extensions() -> Vec<&'static CStr> {
+    vec![
+        XlibSurface::name(),
+        XcbSurface::name(),
+        WaylandSurface::name(),
+    ]
+}
+
+/// Platform surface extensions which are both compiled in for the target and reported by the loader.
+pub fn get_surface_extensions(entry: &Entry) -> SetupResult<Vec<&'static CStr>> {
+    let supported_extensions = entry
+        .enumerate_instance_extension_properties()
+        .context("Failed to enumerate instance extension properties")?;
+    let supported_extension_names = supported_extensions
+        .iter()
+        .map(|properties| unsafe { CStr::from_ptr(properties.extension_name.as_ptr()) })
+        .collect::<Vec<&CStr>>();
+
+    let surface_extensions = get_candidate_surface_extensions()
+        .into_iter()
+        .filter(|extension_name| supported_extension_names.contains(extension_name))
+        .collect::<Vec<&'static CStr>>();
+
+    if surface_extensions.is_empty() {
+        return Err(SetupError::unsupported(
+            "No platform surface extensions supported by the Vulkan loader!",
+        ));
+    }
+    Ok(surface_extensions)
+}
+
+pub fn is_surface_extension_supported(entry: &Entry, extension_name: &CStr) -> SetupResult<bool> {
+    Ok(get_surface_extensions(entry)?.contains(&extension_name))
+}
@@ -1,11 +1,18 @@
-use std::ptr;
+use ash::{vk, Entry, Instance};
+use winit::window::Window;
 
-use ash::{extensions::khr::Win32Surface, vk, Entry, Instance};
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+use crate::setup::error::SetupError;
+use crate::setup::error::{SetupResult, VkResultExt};
 
-use winapi::um::libloaderapi::GetModuleHandleW;
-use winit::{platform::windows::WindowExtWindows, window::Window};
+#[cfg(target_os = "windows")]
+pub fn create(entry: &Entry, instance: &Instance, window: &Window) -> SetupResult<vk::SurfaceKHR> {
+    use std::ptr;
+
+    use ash::extensions::khr::Win32Surface;
+    use winapi::um::libloaderapi::GetModuleHandleW;
+    use winit::platform::windows::WindowExtWindows;
 
-pub fn create(entry: &Entry, instance: &Instance, window: &Window) -> vk::SurfaceKHR {
     let win32_surface_loader = Win32Surface::new(entry, instance);
 
     let create_info = vk::Win32SurfaceCreateInfoKHR::builder()
@@ -16,6 +23,111 @@ pub fn create(entry: &Entry, instance: &Instance, window: &Window) -> vk::Surfac
     unsafe {
         win32_surface_loader
             .create_win32_surface(&create_info, None)
-            .expect("Failed to create win32 surface!")
+            .context("Failed to create win32 surface")
+    }
+}
+
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+pub fn create(entry: &Entry, instance: &Instance, window: &Window) -> SetupResult<vk::SurfaceKHR> {
+    use ash::extensions::khr::{WaylandSurface, XcbSurface, XlibSurface};
+    use winit::platform::unix::WindowExtUnix;
+
+    use super::extensions::is_surface_extension_supported;
+
+    // winit only hands out the handles of the backend the window was actually created with
+    if let (Some(display), Some(surface)) = (window.wayland_display(), window.wayland_surface()) {
+        if is_surface_extension_supported(entry, WaylandSurface::name())? {
+            return create_wayland_surface(entry, instance, display, surface);
+        }
+        return Err(SetupError::unsupported(
+            "Window uses Wayland but VK_KHR_wayland_surface is not supported!",
+        ));
+    }
+
+    let x11_window = window.xlib_window().ok_or_else(|| {
+        SetupError::unsupported("Window exposes neither Wayland nor X11 handles!")
+    })?;
+
+    if let Some(display) = window.xlib_display() {
+        if is_surface_extension_supported(entry, XlibSurface::name())? {
+            return create_xlib_surface(entry, instance, display, x11_window);
+        }
+    }
+    if let Some(connection) = window.xcb_connection() {
+        if is_surface_extension_supported(entry, XcbSurface::name())? {
+            return create_xcb_surface(entry, instance, connection, x11_window);
+        }
+    }
+    Err(SetupError::unsupported(
+        "Window uses X11 but neither VK_KHR_xlib_surface nor VK_KHR_xcb_surface is supported!",
+    ))
+}
+
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+fn create_xlib_surface(
+    entry: &Entry,
+    instance: &Instance,
+    display: *mut std::os::raw::c_void,
+    window: std::os::raw::c_ulong,
+) -> SetupResult<vk::SurfaceKHR> {
+    use ash::extensions::khr::XlibSurface;
+
+    let xlib_surface_loader = XlibSurface::new(entry, instance);
+
+    let create_info = vk::XlibSurfaceCreateInfoKHR::builder()
+        .dpy(display as *mut vk::Display)
+        .window(window as vk::Window)
+        .build();
+
+    unsafe {
+        xlib_surface_loader
+            .create_xlib_surface(&create_info, None)
+            .context("Failed to create xlib surface")
+    }
+}
+
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+fn create_xcb_surface(
+    entry: &Entry,
+    instance: &Instance,
+    connection: *mut std::os::raw::c_void,
+    window: std::os::raw::c_ulong,
+) -> SetupResult<vk::SurfaceKHR> {
+    use ash::extensions::khr::XcbSurface;
+
+    let xcb_surface_loader = XcbSurface::new(entry, instance);
+
+    let create_info = vk::XcbSurfaceCreateInfoKHR::builder()
+        .connection(connection as *mut vk::xcb_connection_t)
+        .window(window as vk::xcb_window_t)
+        .build();
+
+    unsafe {
+        xcb_surface_loader
+            .create_xcb_surface(&create_info, None)
+            .context("Failed to create xcb surface")
+    }
+}
+
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+fn create_wayland_surface(
+    entry: &Entry,
+    instance: &Instance,
+    display: *mut std::os::raw::c_void,
+    surface: *mut std::os::raw::c_void,
+) -> SetupResult<vk::SurfaceKHR> {
+    use ash::extensions::khr::WaylandSurface;
+
+    let wayland_surface_loader = WaylandSurface::new(entry, instance);
+
+    let create_info = vk::WaylandSurfaceCreateInfoKHR::builder()
+        .display(display as *mut vk::wl_display)
+        .surface(surface as *mut vk::wl_surface)
+        .build();
+
+    unsafe {
+        wayland_surface_loader
+            .create_wayland_surface(&create_info, None)
+            .context("Failed to create wayland surface")
     }
 }
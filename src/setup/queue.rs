@@ -0,0 +1,186 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use ash::{
+    extensions::khr::TimelineSemaphore,
+    version::{DeviceV1_0, DeviceV1_2},
+    vk, Device, Entry, Instance,
+};
+
+use crate::setup::error::{SetupResult, VkResultExt};
+
+/// Where a device's timeline semaphores come from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimelineSupport {
+    /// Core since Vulkan 1.2.
+    Core,
+    /// `VK_KHR_timeline_semaphore` on an older device.
+    Extension,
+}
+
+enum TimelineFns {
+    Core,
+    Extension(TimelineSemaphore),
+}
+
+/// A timeline semaphore counting the submissions to one queue. Every submission signals the
+/// next value, so waiting for a value also waits for every submission before it.
+pub struct Timeline {
+    device: Arc<Device>,
+    fns: TimelineFns,
+    semaphore: vk::Semaphore,
+    /// The value the latest submission signals.
+    last_value: AtomicU64,
+}
+
+impl Timeline {
+    pub fn new(
+        entry: &Entry,
+        instance: &Instance,
+        device: &Arc<Device>,
+        support: TimelineSupport,
+    ) -> SetupResult<Self> {
+        let fns = match support {
+            TimelineSupport::Core => TimelineFns::Core,
+            TimelineSupport::Extension => {
+                TimelineFns::Extension(TimelineSemaphore::new(entry, instance))
+            }
+        };
+        let mut semaphore_type_create_info = vk::SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(0)
+            .build();
+        let semaphore_create_info = vk::SemaphoreCreateInfo::builder()
+            .push_next(&mut semaphore_type_create_info)
+            .build();
+        let semaphore = unsafe {
+            device
+                .create_semaphore(&semaphore_create_info, None)
+                .context("Failed to create timeline semaphore")?
+        };
+
+        Ok(Self {
+            device: Arc::clone(device),
+            fns,
+            semaphore,
+            last_value: AtomicU64::new(0),
+        })
+    }
+
+    /// Blocks until the submission signalling `value` has completed; 0 is signalled on creation.
+    pub fn wait(&self, value: u64) -> SetupResult<()> {
+        let semaphores = [self.semaphore];
+        let values = [value];
+        let semaphore_wait_info = vk::SemaphoreWaitInfo::builder()
+            .semaphores(&semaphores)
+            .values(&values)
+            .build();
+        unsafe {
+            match &self.fns {
+                TimelineFns::Core => self.device.wait_semaphores(&semaphore_wait_info, u64::MAX),
+                TimelineFns::Extension(timeline_semaphore) => timeline_semaphore.wait_semaphores(
+                    self.device.handle(),
+                    &semaphore_wait_info,
+                    u64::MAX,
+                ),
+            }
+            .context("Failed to wait for timeline semaphore")
+        }
+    }
+
+    /// Reserves the value the next submission signals.
+    fn next_value(&self) -> u64 {
+        self.last_value.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+impl Drop for Timeline {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_semaphore(self.semaphore, None) };
+    }
+}
+
+/// The semaphores and command buffers of one submission.
+#[derive(Default)]
+pub struct Batch<'a> {
+    pub wait_semaphores: &'a [vk::Semaphore],
+    /// One per wait semaphore.
+    pub wait_stages: &'a [vk::PipelineStageFlags],
+    pub command_buffers: &'a [vk::CommandBuffer],
+    pub signal_semaphores: &'a [vk::Semaphore],
+}
+
+/// A device queue and, when the device has timeline semaphores, the timeline its submissions
+/// signal; without one, the host waits on fences or for the queue to go idle.
+pub struct Queue {
+    pub handle: vk::Queue,
+    pub timeline: Option<Timeline>,
+}
+
+impl Queue {
+    /// Submits `batch`, signalling `fence` and the next timeline value, which is returned.
+    pub fn submit(
+        &self,
+        device: &Device,
+        batch: &Batch,
+        fence: vk::Fence,
+    ) -> SetupResult<Option<u64>> {
+        let submit_info = vk::SubmitInfo::builder()
+            .wait_semaphores(batch.wait_semaphores)
+            .wait_dst_stage_mask(batch.wait_stages)
+            .command_buffers(batch.command_buffers);
+        let submit_result = match &self.timeline {
+            Some(timeline) => {
+                let value = timeline.next_value();
+                let mut signal_semaphores = batch.signal_semaphores.to_vec();
+                signal_semaphores.push(timeline.semaphore);
+                // binary semaphores ignore their values
+                let mut signal_values = vec![0; batch.signal_semaphores.len()];
+                signal_values.push(value);
+                let mut timeline_submit_info = vk::TimelineSemaphoreSubmitInfo::builder()
+                    .signal_semaphore_values(&signal_values)
+                    .build();
+                let submit_info = submit_info
+                    .signal_semaphores(&signal_semaphores)
+                    .push_next(&mut timeline_submit_info)
+                    .build();
+                unsafe { device.queue_submit(self.handle, &[submit_info], fence) }
+                    .map(|_| Some(value))
+            }
+            None => {
+                let submit_info = submit_info
+                    .signal_semaphores(batch.signal_semaphores)
+                    .build();
+                unsafe { device.queue_submit(self.handle, &[submit_info], fence) }.map(|_| None)
+            }
+        };
+        submit_result.context("Failed to submit to queue")
+    }
+
+    /// Submits `command_buffer` and blocks until it has executed, waiting for its timeline
+    /// value or, without a timeline, for the queue to go idle.
+    pub fn submit_and_wait(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+    ) -> SetupResult<()> {
+        let command_buffers = [command_buffer];
+        let batch = Batch {
+            command_buffers: &command_buffers,
+            ..Batch::default()
+        };
+        match (
+            self.submit(device, &batch, vk::Fence::null())?,
+            &self.timeline,
+        ) {
+            (Some(value), Some(timeline)) => timeline.wait(value),
+            _ => unsafe {
+                device
+                    .queue_wait_idle(self.handle)
+                    .context("Failed waiting for queue idle")
+            },
+        }
+    }
+}
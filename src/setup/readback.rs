@@ -0,0 +1,166 @@
+use std::{path::Path, sync::Arc};
+
+use ash::{version::DeviceV1_0, vk};
+use image::{ImageFormat, RgbaImage};
+
+use crate::setup::allocator::Allocator;
+use crate::setup::buffer::{self, begin_single_time_commands, end_single_time_commands};
+use crate::setup::error::{SetupError, SetupResult};
+use crate::setup::queue::Queue;
+
+/// Copies a rendered (resolved, single-sampled) color image back to the host as tightly packed RGBA8.
+/// The image is expected in `layout` and is left in that same layout afterwards.
+/// sRGB formats are returned as stored, i.e. already gamma-encoded, which is what PNG expects.
+pub fn read_image(
+    allocator: &Arc<Allocator>,
+    command_pool: vk::CommandPool,
+    queue: &Queue,
+    image: vk::Image,
+    layout: vk::ImageLayout,
+    format: vk::Format,
+    extent: vk::Extent2D,
+) -> SetupResult<RgbaImage> {
+    let swizzle_bgra = match format {
+        vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => true,
+        vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => false,
+        format => {
+            return Err(SetupError::unsupported(format!(
+                "Unsupported readback format: {:?}",
+                format
+            )))
+        }
+    };
+    let buffer_size = (extent.width * extent.height * 4) as vk::DeviceSize;
+
+    let device = allocator.device();
+    let readback_buffer = buffer::create(
+        allocator,
+        buffer_size,
+        vk::BufferUsageFlags::TRANSFER_DST,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    )?;
+
+    let command_buffer = begin_single_time_commands(device, command_pool)?;
+
+    let subresource_range = vk::ImageSubresourceRange::builder()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .base_mip_level(0)
+        .level_count(1)
+        .base_array_layer(0)
+        .layer_count(1)
+        .build();
+
+    let barrier_transfer = vk::ImageMemoryBarrier::builder()
+        .image(image)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .subresource_range(subresource_range)
+        .old_layout(layout)
+        .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+        .build();
+
+    let region = vk::BufferImageCopy::builder()
+        .buffer_offset(0)
+        .buffer_row_length(0)
+        .buffer_image_height(0)
+        .image_subresource(
+            vk::ImageSubresourceLayers::builder()
+                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                .mip_level(0)
+                .base_array_layer(0)
+                .layer_count(1)
+                .build(),
+        )
+        .image_offset(vk::Offset3D::default())
+        .image_extent(
+            vk::Extent3D::builder()
+                .width(extent.width)
+                .height(extent.height)
+                .depth(1)
+                .build(),
+        )
+        .build();
+
+    let barrier_restore = vk::ImageMemoryBarrier::builder()
+        .image(image)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .subresource_range(subresource_range)
+        .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+        .new_layout(layout)
+        .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+        .dst_access_mask(vk::AccessFlags::default())
+        .build();
+
+    let barrier_host = vk::BufferMemoryBarrier::builder()
+        .buffer(readback_buffer.buffer)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .offset(0)
+        .size(vk::WHOLE_SIZE)
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::HOST_READ)
+        .build();
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::default(),
+            &[],
+            &[],
+            &[barrier_transfer],
+        );
+        device.cmd_copy_image_to_buffer(
+            command_buffer,
+            image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            readback_buffer.buffer,
+            &[region],
+        );
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::default(),
+            &[],
+            &[],
+            &[barrier_restore],
+        );
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::HOST,
+            vk::DependencyFlags::default(),
+            &[],
+            &[barrier_host],
+            &[],
+        );
+    }
+
+    end_single_time_commands(device, command_pool, command_buffer, queue)?;
+
+    let mut pixels = vec![0u8; buffer_size as usize];
+    readback_buffer.read(&mut pixels)?;
+
+    if swizzle_bgra {
+        pixels
+            .chunks_exact_mut(4)
+            .for_each(|pixel| pixel.swap(0, 2));
+    }
+
+    RgbaImage::from_raw(extent.width, extent.height, pixels)
+        .ok_or_else(|| SetupError::unsupported("Readback buffer does not match image extent!"))
+}
+
+pub fn save_png(image: &RgbaImage, path: &Path) -> SetupResult<()> {
+    image
+        .save_with_format(path, ImageFormat::Png)
+        .map_err(|source| SetupError::Image {
+            path: path.to_path_buf(),
+            source,
+        })
+}
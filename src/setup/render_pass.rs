@@ -1,27 +1,40 @@
 use ash::{version::DeviceV1_0, vk, Device, Instance};
 
-use crate::setup::{image, swapchain::SwapchainData};
+use crate::setup::{
+    error::{SetupResult, VkResultExt},
+    image,
+    render_target::RenderTarget,
+};
 
+/// At 1x the render target is drawn to directly; otherwise the multisampled color attachment is
+/// resolved into it.
 pub fn create(
     instance: &Instance,
     device: &Device,
     physical_device: &vk::PhysicalDevice,
-    swapchain_data: &SwapchainData,
+    render_target: &RenderTarget,
     msaa_samples: vk::SampleCountFlags,
-) -> vk::RenderPass {
-    let attachments = [
+) -> SetupResult<vk::RenderPass> {
+    let depth_format = image::find_depth_format(instance, physical_device)?;
+    let multisampled = msaa_samples != vk::SampleCountFlags::TYPE_1;
+    let color_final_layout = if multisampled {
+        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+    } else {
+        render_target.final_layout()
+    };
+    let mut attachments = vec![
         vk::AttachmentDescription::builder()
-            .format(swapchain_data.image_format)
+            .format(render_target.image_format())
             .samples(msaa_samples)
             .load_op(vk::AttachmentLoadOp::CLEAR)
             .store_op(vk::AttachmentStoreOp::STORE)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
             .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .final_layout(color_final_layout)
             .build(),
         vk::AttachmentDescription::builder()
-            .format(image::find_depth_format(instance, physical_device))
+            .format(depth_format)
             .samples(msaa_samples)
             .load_op(vk::AttachmentLoadOp::CLEAR)
             .store_op(vk::AttachmentStoreOp::DONT_CARE)
@@ -30,17 +43,21 @@ pub fn create(
             .initial_layout(vk::ImageLayout::UNDEFINED)
             .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
             .build(),
-        vk::AttachmentDescription::builder()
-            .format(swapchain_data.image_format)
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .load_op(vk::AttachmentLoadOp::DONT_CARE)
-            .store_op(vk::AttachmentStoreOp::STORE)
-            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)
-            .build(),
     ];
+    if multisampled {
+        attachments.push(
+            vk::AttachmentDescription::builder()
+                .format(render_target.image_format())
+                .samples(vk::SampleCountFlags::TYPE_1)
+                .load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .final_layout(render_target.final_layout())
+                .build(),
+        );
+    }
 
     let color_attachment_refs = [vk::AttachmentReference::builder()
         .attachment(0)
@@ -57,12 +74,14 @@ pub fn create(
         .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
         .build()];
 
-    let subpasses = [vk::SubpassDescription::builder()
+    let mut subpass_builder = vk::SubpassDescription::builder()
         .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
         .color_attachments(&color_attachment_refs)
-        .depth_stencil_attachment(&depth_stencil_attachment_ref)
-        .resolve_attachments(&resolve_attachment_refs)
-        .build()];
+        .depth_stencil_attachment(&depth_stencil_attachment_ref);
+    if multisampled {
+        subpass_builder = subpass_builder.resolve_attachments(&resolve_attachment_refs);
+    }
+    let subpasses = [subpass_builder.build()];
 
     let dependencies = [vk::SubpassDependency::builder()
         .src_subpass(vk::SUBPASS_EXTERNAL)
@@ -83,7 +102,7 @@ pub fn create(
     let render_pass = unsafe {
         device
             .create_render_pass(&render_pass_create_info, None)
-            .expect("Failed to create render pass!")
+            .context("Failed to create render pass")?
     };
-    render_pass
+    Ok(render_pass)
 }
@@ -0,0 +1,72 @@
+use ash::vk;
+
+use crate::setup::{offscreen::OffscreenData, swapchain::SwapchainData};
+
+/// Images the render pass resolves into: either presentable swapchain images or offscreen ones.
+pub enum RenderTarget {
+    Swapchain(SwapchainData),
+    Offscreen(OffscreenData),
+}
+
+impl RenderTarget {
+    pub fn images(&self) -> Vec<vk::Image> {
+        match self {
+            RenderTarget::Swapchain(swapchain_data) => swapchain_data.swapchain_images.clone(),
+            RenderTarget::Offscreen(offscreen_data) => offscreen_data
+                .images
+                .iter()
+                .map(|image| image.image)
+                .collect(),
+        }
+    }
+
+    pub fn image_views(&self) -> Vec<vk::ImageView> {
+        match self {
+            RenderTarget::Swapchain(swapchain_data) => swapchain_data.swapchain_image_views.clone(),
+            RenderTarget::Offscreen(offscreen_data) => offscreen_data
+                .images
+                .iter()
+                .map(|image| image.view)
+                .collect(),
+        }
+    }
+
+    pub fn image_format(&self) -> vk::Format {
+        match self {
+            RenderTarget::Swapchain(swapchain_data) => swapchain_data.image_format,
+            RenderTarget::Offscreen(offscreen_data) => offscreen_data.image_format,
+        }
+    }
+
+    pub fn image_extent(&self) -> vk::Extent2D {
+        match self {
+            RenderTarget::Swapchain(swapchain_data) => swapchain_data.image_extent,
+            RenderTarget::Offscreen(offscreen_data) => offscreen_data.image_extent,
+        }
+    }
+
+    pub fn swapchain_data(&self) -> Option<&SwapchainData> {
+        match self {
+            RenderTarget::Swapchain(swapchain_data) => Some(swapchain_data),
+            RenderTarget::Offscreen(_) => None,
+        }
+    }
+
+    /// Whether the resolved images can be copied back to the host.
+    pub fn supports_readback(&self) -> bool {
+        match self {
+            RenderTarget::Swapchain(swapchain_data) => swapchain_data
+                .image_usage
+                .contains(vk::ImageUsageFlags::TRANSFER_SRC),
+            RenderTarget::Offscreen(_) => true,
+        }
+    }
+
+    /// Layout the resolved color attachment is left in once the render pass ends.
+    pub fn final_layout(&self) -> vk::ImageLayout {
+        match self {
+            RenderTarget::Swapchain(_) => vk::ImageLayout::PRESENT_SRC_KHR,
+            RenderTarget::Offscreen(_) => vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    time::Duration,
+};
+
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::setup::error::{SetupError, SetupResult};
+
+/// Editors tend to save in several steps; changes within this window are reported once.
+const WATCH_DELAY: Duration = Duration::from_millis(200);
+
+/// Compiles GLSL and HLSL sources to SPIR-V in process.
+pub struct ShaderCompiler {
+    compiler: shaderc::Compiler,
+}
+
+impl ShaderCompiler {
+    pub fn new() -> SetupResult<Self> {
+        let compiler = shaderc::Compiler::new()
+            .ok_or_else(|| SetupError::unsupported("Failed to initialize the shader compiler!"))?;
+        Ok(Self { compiler })
+    }
+
+    /// The stage comes from the extension as with `glslc`: `.vert`, `.frag`, `.comp`, `.geom`,
+    /// `.tesc` or `.tese`. Sources ending in `.hlsl` are HLSL with the stage extension before it,
+    /// e.g. `shader.frag.hlsl`. Errors carry the compiler's `file:line: error` messages.
+    pub fn compile(&mut self, source: &Path) -> SetupResult<Vec<u32>> {
+        let hlsl = source
+            .extension()
+            .is_some_and(|extension| extension == "hlsl");
+        let stage_path = if hlsl {
+            source.file_stem().map(Path::new)
+        } else {
+            Some(source)
+        };
+        let shader_kind = match stage_path
+            .and_then(Path::extension)
+            .and_then(|extension| extension.to_str())
+        {
+            Some("vert") => shaderc::ShaderKind::Vertex,
+            Some("frag") => shaderc::ShaderKind::Fragment,
+            Some("comp") => shaderc::ShaderKind::Compute,
+            Some("geom") => shaderc::ShaderKind::Geometry,
+            Some("tesc") => shaderc::ShaderKind::TessControl,
+            Some("tese") => shaderc::ShaderKind::TessEvaluation,
+            _ => {
+                return Err(SetupError::ShaderCompilation {
+                    path: source.to_path_buf(),
+                    message: String::from("unknown shader stage, expected a .vert, .frag, .comp, .geom, .tesc or .tese extension"),
+                })
+            }
+        };
+
+        let source_text = fs::read_to_string(source).map_err(|error| SetupError::Io {
+            path: source.to_path_buf(),
+            source: error,
+        })?;
+        let mut options = shaderc::CompileOptions::new()
+            .ok_or_else(|| SetupError::unsupported("Failed to create shader compile options!"))?;
+        if hlsl {
+            options.set_source_language(shaderc::SourceLanguage::HLSL);
+        }
+
+        let artifact = self
+            .compiler
+            .compile_into_spirv(
+                &source_text,
+                shader_kind,
+                &source.display().to_string(),
+                "main",
+                Some(&options),
+            )
+            .map_err(|error| SetupError::ShaderCompilation {
+                path: source.to_path_buf(),
+                message: match error {
+                    shaderc::Error::CompilationError(_, message) => message,
+                    error => error.to_string(),
+                },
+            })?;
+        Ok(artifact.as_binary().to_vec())
+    }
+}
+
+/// Reports writes to a fixed set of shader sources. Their directories are watched rather than
+/// the files, since editors often save by replacing a file.
+pub struct ShaderWatcher {
+    // events stop once the watcher is dropped
+    _watcher: RecommendedWatcher,
+    events: Receiver<DebouncedEvent>,
+    /// `(as given, canonical)`
+    sources: Vec<(PathBuf, PathBuf)>,
+}
+
+impl ShaderWatcher {
+    pub fn new(sources: &[PathBuf]) -> SetupResult<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher =
+            notify::watcher(sender, WATCH_DELAY).map_err(|source| SetupError::Watch {
+                path: sources.first().cloned().unwrap_or_default(),
+                source,
+            })?;
+
+        let mut directories: Vec<&Path> = vec![];
+        for source in sources {
+            let directory = source
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new("."));
+            if !directories.contains(&directory) {
+                watcher
+                    .watch(directory, RecursiveMode::NonRecursive)
+                    .map_err(|source| SetupError::Watch {
+                        path: directory.to_path_buf(),
+                        source,
+                    })?;
+                directories.push(directory);
+            }
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            sources: sources
+                .iter()
+                .map(|source| (source.clone(), canonicalize(source)))
+                .collect(),
+        })
+    }
+
+    /// Sources written, created or renamed into place since the last call, as given to `new`;
+    /// does not block.
+    pub fn changed_sources(&self) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = vec![];
+        for event in self.events.try_iter() {
+            let path = match event {
+                DebouncedEvent::Write(path)
+                | DebouncedEvent::Create(path)
+                | DebouncedEvent::Rename(_, path) => canonicalize(&path),
+                _ => continue,
+            };
+            for (source, canonical) in &self.sources {
+                if *canonical == path && !changed.contains(source) {
+                    changed.push(source.clone());
+                }
+            }
+        }
+        changed
+    }
+}
+
+/// Event paths and configured paths may differ in form, e.g. relative versus absolute.
+fn canonicalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
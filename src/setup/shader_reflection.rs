@@ -0,0 +1,943 @@
+use std::{collections::HashMap, path::Path};
+
+use ash::vk;
+
+use crate::setup::error::{SetupError, SetupResult};
+
+const MAGIC_NUMBER: u32 = 0x0723_0203;
+const HEADER_WORD_COUNT: usize = 5;
+
+// opcodes
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+// decorations
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+// storage classes
+const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_CLASS_INPUT: u32 = 1;
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+// image dimensions and sampled operands
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+const IMAGE_SAMPLED_STORAGE: u32 = 2;
+
+/// A resource the shader reads through a descriptor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    pub count: u32,
+}
+
+/// A vertex shader input and the format matching its type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VertexInput {
+    pub location: u32,
+    pub format: vk::Format,
+}
+
+/// What one shader stage expects from the pipeline, as declared in its SPIR-V.
+#[derive(Clone, Debug)]
+pub struct ShaderInterface {
+    pub stage: vk::ShaderStageFlags,
+    pub descriptor_bindings: Vec<DescriptorBinding>,
+    /// Bytes of the push-constant block this stage reads, as `(offset, size)`.
+    pub push_constants: Option<(u32, u32)>,
+    /// Only filled in for vertex shaders; built-ins such as `gl_VertexIndex` are left out.
+    pub vertex_inputs: Vec<VertexInput>,
+}
+
+#[derive(Clone, Debug)]
+enum Type {
+    Scalar {
+        width: u32,
+        float: bool,
+        signed: bool,
+    },
+    Vector {
+        component: u32,
+        count: u32,
+    },
+    Matrix {
+        column: u32,
+        count: u32,
+    },
+    Image {
+        dim: u32,
+        sampled: u32,
+    },
+    Sampler,
+    SampledImage,
+    Array {
+        element: u32,
+        length: u32,
+    },
+    RuntimeArray,
+    Struct {
+        members: Vec<u32>,
+    },
+    Pointer {
+        pointee: u32,
+    },
+    Other,
+}
+
+#[derive(Default)]
+struct Decorations {
+    binding: Option<u32>,
+    set: Option<u32>,
+    location: Option<u32>,
+    array_stride: Option<u32>,
+    buffer_block: bool,
+    built_in: bool,
+    member_offsets: HashMap<u32, u32>,
+    member_matrix_strides: HashMap<u32, u32>,
+    member_built_in: bool,
+}
+
+struct Module<'a> {
+    path: &'a Path,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<u32, Decorations>,
+    /// `(id, pointer type, storage class)`
+    variables: Vec<(u32, u32, u32)>,
+    execution_model: Option<u32>,
+}
+
+/// Parses the SPIR-V words of one shader module, as returned by `graphics_pipeline::read_shader`.
+pub fn reflect(path: &Path, words: &[u32]) -> SetupResult<ShaderInterface> {
+    let module = Module::parse(path, words)?;
+    let stage = match module.execution_model {
+        Some(0) => vk::ShaderStageFlags::VERTEX,
+        Some(1) => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+        Some(2) => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+        Some(3) => vk::ShaderStageFlags::GEOMETRY,
+        Some(4) => vk::ShaderStageFlags::FRAGMENT,
+        Some(5) => vk::ShaderStageFlags::COMPUTE,
+        Some(model) => return Err(module.error(format!("unsupported execution model {}", model))),
+        None => return Err(module.error("no entry point")),
+    };
+
+    let mut interface = ShaderInterface {
+        stage,
+        descriptor_bindings: vec![],
+        push_constants: None,
+        vertex_inputs: vec![],
+    };
+    for &(id, pointer_type, storage_class) in &module.variables {
+        let pointee = match module.types.get(&pointer_type) {
+            Some(Type::Pointer { pointee }) => *pointee,
+            _ => return Err(module.error(format!("variable %{} is not a pointer", id))),
+        };
+        match storage_class {
+            STORAGE_CLASS_UNIFORM_CONSTANT
+            | STORAGE_CLASS_UNIFORM
+            | STORAGE_CLASS_STORAGE_BUFFER => interface
+                .descriptor_bindings
+                .push(module.descriptor_binding(id, pointee, storage_class)?),
+            STORAGE_CLASS_PUSH_CONSTANT => {
+                interface.push_constants = Some(module.push_constant_range(pointee)?)
+            }
+            STORAGE_CLASS_INPUT if stage == vk::ShaderStageFlags::VERTEX => {
+                if let Some(vertex_input) = module.vertex_input(id, pointee)? {
+                    interface.vertex_inputs.push(vertex_input);
+                }
+            }
+            _ => {}
+        }
+    }
+    interface
+        .descriptor_bindings
+        .sort_by_key(|binding| (binding.set, binding.binding));
+    interface
+        .vertex_inputs
+        .sort_by_key(|vertex_input| vertex_input.location);
+    Ok(interface)
+}
+
+/// Combines the bindings of all stages into one layout binding per slot, with the stages that
+/// use it. Only set 0 is bound by the renderer, and stages have to agree on every slot.
+pub fn descriptor_set_layout_bindings(
+    interfaces: &[ShaderInterface],
+) -> SetupResult<Vec<vk::DescriptorSetLayoutBinding>> {
+    let mut bindings: Vec<vk::DescriptorSetLayoutBinding> = vec![];
+    for interface in interfaces {
+        for binding in &interface.descriptor_bindings {
+            if binding.set != 0 {
+                return Err(SetupError::unsupported(format!(
+                    "Shaders use descriptor set {}, but only set 0 is bound!",
+                    binding.set
+                )));
+            }
+            match bindings
+                .iter_mut()
+                .find(|existing| existing.binding == binding.binding)
+            {
+                Some(existing) => {
+                    if existing.descriptor_type != binding.descriptor_type
+                        || existing.descriptor_count != binding.count
+                    {
+                        return Err(SetupError::unsupported(format!(
+                            "Shader stages disagree on binding {}: {:?}[{}] and {:?}[{}]!",
+                            binding.binding,
+                            existing.descriptor_type,
+                            existing.descriptor_count,
+                            binding.descriptor_type,
+                            binding.count
+                        )));
+                    }
+                    existing.stage_flags |= interface.stage;
+                }
+                None => bindings.push(
+                    vk::DescriptorSetLayoutBinding::builder()
+                        .binding(binding.binding)
+                        .descriptor_type(binding.descriptor_type)
+                        .descriptor_count(binding.count)
+                        .stage_flags(interface.stage)
+                        .build(),
+                ),
+            }
+        }
+    }
+    bindings.sort_by_key(|binding| binding.binding);
+    Ok(bindings)
+}
+
+/// One range per stage that declares a push-constant block.
+pub fn push_constant_ranges(interfaces: &[ShaderInterface]) -> Vec<vk::PushConstantRange> {
+    interfaces
+        .iter()
+        .filter_map(|interface| {
+            interface.push_constants.map(|(offset, size)| {
+                vk::PushConstantRange::builder()
+                    .stage_flags(interface.stage)
+                    .offset(offset)
+                    .size(size)
+                    .build()
+            })
+        })
+        .collect()
+}
+
+/// Checks that every input the vertex shader reads is provided by `attributes` with the same
+/// format.
+pub fn check_vertex_inputs(
+    path: &Path,
+    interface: &ShaderInterface,
+    attributes: &[vk::VertexInputAttributeDescription],
+) -> SetupResult<()> {
+    for vertex_input in &interface.vertex_inputs {
+        let attribute = attributes
+            .iter()
+            .find(|attribute| attribute.location == vertex_input.location);
+        match attribute {
+            None => {
+                return Err(SetupError::Shader {
+                    path: path.to_path_buf(),
+                    message: format!(
+                        "input at location {} ({:?}) is missing from the vertex layout",
+                        vertex_input.location, vertex_input.format
+                    ),
+                })
+            }
+            Some(attribute) if attribute.format != vertex_input.format => {
+                return Err(SetupError::Shader {
+                    path: path.to_path_buf(),
+                    message: format!(
+                        "input at location {} is {:?}, but the vertex layout provides {:?}",
+                        vertex_input.location, vertex_input.format, attribute.format
+                    ),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+impl<'a> Module<'a> {
+    fn parse(path: &'a Path, words: &[u32]) -> SetupResult<Self> {
+        let mut module = Module {
+            path,
+            types: HashMap::new(),
+            constants: HashMap::new(),
+            decorations: HashMap::new(),
+            variables: vec![],
+            execution_model: None,
+        };
+        if words.len() < HEADER_WORD_COUNT || words[0] != MAGIC_NUMBER {
+            return Err(module.error("not a SPIR-V module"));
+        }
+
+        let mut offset = HEADER_WORD_COUNT;
+        while offset < words.len() {
+            let word_count = (words[offset] >> 16) as usize;
+            let opcode = words[offset] & 0xffff;
+            if word_count == 0 || offset + word_count > words.len() {
+                return Err(module.error(format!("truncated instruction at word {}", offset)));
+            }
+            module.parse_instruction(opcode, &words[offset + 1..offset + word_count]);
+            offset += word_count;
+        }
+        Ok(module)
+    }
+
+    /// `operands` excludes the opcode word; operand counts are not validated beyond what is read.
+    fn parse_instruction(&mut self, opcode: u32, operands: &[u32]) {
+        let operand = |index: usize| operands.get(index).copied().unwrap_or(0);
+        match opcode {
+            OP_ENTRY_POINT if self.execution_model.is_none() => {
+                self.execution_model = Some(operand(0))
+            }
+            OP_TYPE_BOOL => {
+                self.types.insert(operand(0), Type::Other);
+            }
+            OP_TYPE_INT => {
+                self.types.insert(
+                    operand(0),
+                    Type::Scalar {
+                        width: operand(1),
+                        float: false,
+                        signed: operand(2) != 0,
+                    },
+                );
+            }
+            OP_TYPE_FLOAT => {
+                self.types.insert(
+                    operand(0),
+                    Type::Scalar {
+                        width: operand(1),
+                        float: true,
+                        signed: true,
+                    },
+                );
+            }
+            OP_TYPE_VECTOR => {
+                self.types.insert(
+                    operand(0),
+                    Type::Vector {
+                        component: operand(1),
+                        count: operand(2),
+                    },
+                );
+            }
+            OP_TYPE_MATRIX => {
+                self.types.insert(
+                    operand(0),
+                    Type::Matrix {
+                        column: operand(1),
+                        count: operand(2),
+                    },
+                );
+            }
+            OP_TYPE_IMAGE => {
+                self.types.insert(
+                    operand(0),
+                    Type::Image {
+                        dim: operand(2),
+                        sampled: operand(6),
+                    },
+                );
+            }
+            OP_TYPE_SAMPLER => {
+                self.types.insert(operand(0), Type::Sampler);
+            }
+            OP_TYPE_SAMPLED_IMAGE => {
+                self.types.insert(operand(0), Type::SampledImage);
+            }
+            OP_TYPE_ARRAY => {
+                let length = self.constants.get(&operand(2)).copied().unwrap_or(0);
+                self.types.insert(
+                    operand(0),
+                    Type::Array {
+                        element: operand(1),
+                        length,
+                    },
+                );
+            }
+            OP_TYPE_RUNTIME_ARRAY => {
+                self.types.insert(operand(0), Type::RuntimeArray);
+            }
+            OP_TYPE_STRUCT => {
+                self.types.insert(
+                    operand(0),
+                    Type::Struct {
+                        members: operands.get(1..).unwrap_or(&[]).to_vec(),
+                    },
+                );
+            }
+            OP_TYPE_POINTER => {
+                self.types.insert(
+                    operand(0),
+                    Type::Pointer {
+                        pointee: operand(2),
+                    },
+                );
+            }
+            // only 32-bit integer constants matter, as array lengths
+            OP_CONSTANT => {
+                self.constants.insert(operand(1), operand(2));
+            }
+            OP_VARIABLE => self.variables.push((operand(1), operand(0), operand(2))),
+            OP_DECORATE => {
+                let decorations = self.decorations.entry(operand(0)).or_default();
+                match operand(1) {
+                    DECORATION_BINDING => decorations.binding = Some(operand(2)),
+                    DECORATION_DESCRIPTOR_SET => decorations.set = Some(operand(2)),
+                    DECORATION_LOCATION => decorations.location = Some(operand(2)),
+                    DECORATION_ARRAY_STRIDE => decorations.array_stride = Some(operand(2)),
+                    DECORATION_BUFFER_BLOCK => decorations.buffer_block = true,
+                    DECORATION_BUILT_IN => decorations.built_in = true,
+                    _ => {}
+                }
+            }
+            OP_MEMBER_DECORATE => {
+                let decorations = self.decorations.entry(operand(0)).or_default();
+                match operand(2) {
+                    DECORATION_OFFSET => {
+                        decorations.member_offsets.insert(operand(1), operand(3));
+                    }
+                    DECORATION_MATRIX_STRIDE => {
+                        decorations
+                            .member_matrix_strides
+                            .insert(operand(1), operand(3));
+                    }
+                    DECORATION_BUILT_IN => decorations.member_built_in = true,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn error<S: Into<String>>(&self, message: S) -> SetupError {
+        SetupError::Shader {
+            path: self.path.to_path_buf(),
+            message: message.into(),
+        }
+    }
+
+    fn type_of(&self, id: u32) -> SetupResult<&Type> {
+        self.types
+            .get(&id)
+            .ok_or_else(|| self.error(format!("unknown type %{}", id)))
+    }
+
+    fn decorations(&self, id: u32) -> Option<&Decorations> {
+        self.decorations.get(&id)
+    }
+
+    fn descriptor_binding(
+        &self,
+        variable: u32,
+        pointee: u32,
+        storage_class: u32,
+    ) -> SetupResult<DescriptorBinding> {
+        let decorations = self.decorations(variable);
+        let binding = decorations
+            .and_then(|decorations| decorations.binding)
+            .ok_or_else(|| self.error(format!("resource %{} has no binding", variable)))?;
+        let set = decorations
+            .and_then(|decorations| decorations.set)
+            .unwrap_or(0);
+
+        // arrays of resources take one descriptor per element
+        let mut count = 1;
+        let mut type_id = pointee;
+        let descriptor_type = loop {
+            match self.type_of(type_id)? {
+                Type::Array { element, length } => {
+                    count *= length;
+                    type_id = *element;
+                }
+                Type::RuntimeArray => {
+                    return Err(self.error(format!(
+                        "binding {} is a runtime-sized array, which is not supported",
+                        binding
+                    )))
+                }
+                Type::SampledImage => break vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                Type::Sampler => break vk::DescriptorType::SAMPLER,
+                Type::Image { dim, sampled } => {
+                    break match (*dim, *sampled) {
+                        (DIM_SUBPASS_DATA, _) => vk::DescriptorType::INPUT_ATTACHMENT,
+                        (DIM_BUFFER, IMAGE_SAMPLED_STORAGE) => {
+                            vk::DescriptorType::STORAGE_TEXEL_BUFFER
+                        }
+                        (DIM_BUFFER, _) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+                        (_, IMAGE_SAMPLED_STORAGE) => vk::DescriptorType::STORAGE_IMAGE,
+                        _ => vk::DescriptorType::SAMPLED_IMAGE,
+                    }
+                }
+                Type::Struct { .. } => {
+                    let buffer_block = self
+                        .decorations(type_id)
+                        .is_some_and(|decorations| decorations.buffer_block);
+                    break if storage_class == STORAGE_CLASS_STORAGE_BUFFER || buffer_block {
+                        vk::DescriptorType::STORAGE_BUFFER
+                    } else {
+                        vk::DescriptorType::UNIFORM_BUFFER
+                    };
+                }
+                other => {
+                    return Err(self.error(format!(
+                        "binding {} has unsupported type {:?}",
+                        binding, other
+                    )))
+                }
+            }
+        };
+
+        Ok(DescriptorBinding {
+            set,
+            binding,
+            descriptor_type,
+            count,
+        })
+    }
+
+    fn push_constant_range(&self, block: u32) -> SetupResult<(u32, u32)> {
+        let members = match self.type_of(block)? {
+            Type::Struct { members } => members,
+            _ => return Err(self.error("push constant block is not a struct")),
+        };
+        let offsets = self
+            .decorations(block)
+            .map(|decorations| &decorations.member_offsets);
+
+        let mut start = u32::MAX;
+        let mut end = 0;
+        for (index, member) in members.iter().enumerate() {
+            let offset = offsets
+                .and_then(|offsets| offsets.get(&(index as u32)))
+                .copied()
+                .ok_or_else(|| {
+                    self.error(format!("push constant member {} has no offset", index))
+                })?;
+            let matrix_stride = self
+                .decorations(block)
+                .and_then(|decorations| decorations.member_matrix_strides.get(&(index as u32)))
+                .copied();
+            start = start.min(offset);
+            end = end.max(offset + self.size_of(*member, matrix_stride)?);
+        }
+        if members.is_empty() {
+            return Ok((0, 0));
+        }
+        Ok((start, end - start))
+    }
+
+    /// Size in bytes of a type inside an explicitly laid out block.
+    fn size_of(&self, id: u32, matrix_stride: Option<u32>) -> SetupResult<u32> {
+        Ok(match self.type_of(id)? {
+            Type::Scalar { width, .. } => width / 8,
+            Type::Vector { component, count } => self.size_of(*component, None)? * count,
+            Type::Matrix { column, count } => match matrix_stride {
+                Some(stride) => stride * count,
+                None => self.size_of(*column, None)? * count,
+            },
+            Type::Array { element, length } => {
+                let stride = match self
+                    .decorations(id)
+                    .and_then(|decorations| decorations.array_stride)
+                {
+                    Some(stride) => stride,
+                    None => self.size_of(*element, matrix_stride)?,
+                };
+                stride * length
+            }
+            Type::Struct { members } => {
+                let mut size = 0;
+                for (index, member) in members.iter().enumerate() {
+                    let decorations = self.decorations(id);
+                    let offset = decorations
+                        .and_then(|decorations| decorations.member_offsets.get(&(index as u32)))
+                        .copied()
+                        .unwrap_or(size);
+                    let matrix_stride = decorations.and_then(|decorations| {
+                        decorations
+                            .member_matrix_strides
+                            .get(&(index as u32))
+                            .copied()
+                    });
+                    size = size.max(offset + self.size_of(*member, matrix_stride)?);
+                }
+                size
+            }
+            other => return Err(self.error(format!("{:?} has no size in a block", other))),
+        })
+    }
+
+    fn vertex_input(&self, variable: u32, pointee: u32) -> SetupResult<Option<VertexInput>> {
+        let decorations = self.decorations(variable);
+        let is_built_in = decorations.is_some_and(|decorations| decorations.built_in)
+            || self
+                .decorations(pointee)
+                .is_some_and(|decorations| decorations.member_built_in);
+        if is_built_in {
+            return Ok(None);
+        }
+        let location = decorations
+            .and_then(|decorations| decorations.location)
+            .ok_or_else(|| self.error(format!("vertex input %{} has no location", variable)))?;
+
+        let (scalar, count) = match self.type_of(pointee)? {
+            Type::Scalar { .. } => (pointee, 1),
+            Type::Vector { component, count } => (*component, *count),
+            other => {
+                return Err(self.error(format!(
+                    "vertex input at location {} has unsupported type {:?}",
+                    location, other
+                )))
+            }
+        };
+        let format = match (self.type_of(scalar)?, count) {
+            (
+                Type::Scalar {
+                    width: 32,
+                    float: true,
+                    ..
+                },
+                1,
+            ) => vk::Format::R32_SFLOAT,
+            (
+                Type::Scalar {
+                    width: 32,
+                    float: true,
+                    ..
+                },
+                2,
+            ) => vk::Format::R32G32_SFLOAT,
+            (
+                Type::Scalar {
+                    width: 32,
+                    float: true,
+                    ..
+                },
+                3,
+            ) => vk::Format::R32G32B32_SFLOAT,
+            (
+                Type::Scalar {
+                    width: 32,
+                    float: true,
+                    ..
+                },
+                4,
+            ) => vk::Format::R32G32B32A32_SFLOAT,
+            (
+                Type::Scalar {
+                    width: 32,
+                    signed: true,
+                    ..
+                },
+                1,
+            ) => vk::Format::R32_SINT,
+            (
+                Type::Scalar {
+                    width: 32,
+                    signed: true,
+                    ..
+                },
+                2,
+            ) => vk::Format::R32G32_SINT,
+            (
+                Type::Scalar {
+                    width: 32,
+                    signed: true,
+                    ..
+                },
+                3,
+            ) => vk::Format::R32G32B32_SINT,
+            (
+                Type::Scalar {
+                    width: 32,
+                    signed: true,
+                    ..
+                },
+                4,
+            ) => vk::Format::R32G32B32A32_SINT,
+            (Type::Scalar { width: 32, .. }, 1) => vk::Format::R32_UINT,
+            (Type::Scalar { width: 32, .. }, 2) => vk::Format::R32G32_UINT,
+            (Type::Scalar { width: 32, .. }, 3) => vk::Format::R32G32B32_UINT,
+            (Type::Scalar { width: 32, .. }, 4) => vk::Format::R32G32B32A32_UINT,
+            (other, _) => {
+                return Err(self.error(format!(
+                    "vertex input at location {} has unsupported component type {:?}",
+                    location, other
+                )))
+            }
+        };
+        Ok(Some(VertexInput { location, format }))
+    }
+}
+
+/// Whether pipelines for shaders with the interfaces `b` can use a pipeline layout and descriptor
+/// sets created for `a`: both have to declare the same bindings and push-constant ranges.
+pub fn same_pipeline_layout(a: &[ShaderInterface], b: &[ShaderInterface]) -> SetupResult<bool> {
+    let layout_key = |interfaces| -> SetupResult<_> {
+        let bindings = descriptor_set_layout_bindings(interfaces)?
+            .iter()
+            .map(|binding| {
+                (
+                    binding.binding,
+                    binding.descriptor_type,
+                    binding.descriptor_count,
+                    binding.stage_flags,
+                )
+            })
+            .collect::<Vec<_>>();
+        let ranges = push_constant_ranges(interfaces)
+            .iter()
+            .map(|range| (range.stage_flags, range.offset, range.size))
+            .collect::<Vec<_>>();
+        Ok((bindings, ranges))
+    };
+    Ok(layout_key(a)? == layout_key(b)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use super::*;
+    use crate::structs::Vertex;
+
+    fn bundled_shader(name: &str) -> (PathBuf, Vec<u32>) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/shaders")
+            .join(name);
+        let bytes = fs::read(&path).unwrap();
+        let words = bytes.chunks_exact(4).map(LittleEndian::read_u32).collect();
+        (path, words)
+    }
+
+    fn reflect_bundled(name: &str) -> ShaderInterface {
+        let (path, words) = bundled_shader(name);
+        reflect(&path, &words).unwrap()
+    }
+
+    fn instruction(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    /// A vertex shader declaring `layout(push_constant) uniform { layout(offset = 16) mat4 m;
+    /// vec4 c; }` and nothing else.
+    fn push_constant_module() -> Vec<u32> {
+        let (float, vec4, mat4, block, pointer, variable) = (2, 3, 4, 5, 6, 7);
+        let mut words = vec![MAGIC_NUMBER, 0x0001_0000, 0, 8, 0];
+        // OpEntryPoint Vertex %1 "main"
+        words.extend(instruction(OP_ENTRY_POINT, &[0, 1, 0x6e69_616d, 0]));
+        words.extend(instruction(
+            OP_MEMBER_DECORATE,
+            &[block, 0, DECORATION_OFFSET, 16],
+        ));
+        words.extend(instruction(
+            OP_MEMBER_DECORATE,
+            &[block, 0, DECORATION_MATRIX_STRIDE, 16],
+        ));
+        words.extend(instruction(
+            OP_MEMBER_DECORATE,
+            &[block, 1, DECORATION_OFFSET, 80],
+        ));
+        words.extend(instruction(OP_TYPE_FLOAT, &[float, 32]));
+        words.extend(instruction(OP_TYPE_VECTOR, &[vec4, float, 4]));
+        words.extend(instruction(OP_TYPE_MATRIX, &[mat4, vec4, 4]));
+        words.extend(instruction(OP_TYPE_STRUCT, &[block, mat4, vec4]));
+        words.extend(instruction(
+            OP_TYPE_POINTER,
+            &[pointer, STORAGE_CLASS_PUSH_CONSTANT, block],
+        ));
+        words.extend(instruction(
+            OP_VARIABLE,
+            &[pointer, variable, STORAGE_CLASS_PUSH_CONSTANT],
+        ));
+        words
+    }
+
+    #[test]
+    fn vertex_shader_interface() {
+        let interface = reflect_bundled("vert.spv");
+        assert_eq!(interface.stage, vk::ShaderStageFlags::VERTEX);
+        assert_eq!(
+            interface.descriptor_bindings,
+            vec![DescriptorBinding {
+                set: 0,
+                binding: 0,
+                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                count: 1,
+            }]
+        );
+        // the per-draw object transform
+        assert_eq!(interface.push_constants, Some((0, 64)));
+
+        let formats = [
+            vk::Format::R32G32B32_SFLOAT,
+            vk::Format::R32G32B32_SFLOAT,
+            vk::Format::R32G32_SFLOAT,
+            vk::Format::R32G32B32_SFLOAT,
+            vk::Format::R32G32B32A32_SFLOAT,
+        ];
+        let expected = formats
+            .iter()
+            .enumerate()
+            .map(|(location, &format)| VertexInput {
+                location: location as u32,
+                format,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(interface.vertex_inputs, expected);
+    }
+
+    #[test]
+    fn fragment_shader_interface() {
+        let interface = reflect_bundled("frag.spv");
+        assert_eq!(interface.stage, vk::ShaderStageFlags::FRAGMENT);
+        assert!(interface.vertex_inputs.is_empty());
+        assert_eq!(interface.push_constants, None);
+
+        let bindings = interface
+            .descriptor_bindings
+            .iter()
+            .map(|binding| {
+                (
+                    binding.set,
+                    binding.binding,
+                    binding.descriptor_type,
+                    binding.count,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bindings,
+            vec![
+                (0, 1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1),
+                (0, 2, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1),
+                (0, 3, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1),
+                (0, 4, vk::DescriptorType::UNIFORM_BUFFER, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn stages_are_merged_into_one_layout() {
+        let interfaces = [reflect_bundled("vert.spv"), reflect_bundled("frag.spv")];
+        let bindings = descriptor_set_layout_bindings(&interfaces)
+            .unwrap()
+            .iter()
+            .map(|binding| (binding.binding, binding.stage_flags))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bindings,
+            vec![
+                (0, vk::ShaderStageFlags::VERTEX),
+                (1, vk::ShaderStageFlags::FRAGMENT),
+                (2, vk::ShaderStageFlags::FRAGMENT),
+                (3, vk::ShaderStageFlags::FRAGMENT),
+                (4, vk::ShaderStageFlags::FRAGMENT),
+            ]
+        );
+        let ranges = push_constant_ranges(&interfaces)
+            .iter()
+            .map(|range| (range.stage_flags, range.offset, range.size))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, vec![(vk::ShaderStageFlags::VERTEX, 0, 64)]);
+        assert!(same_pipeline_layout(&interfaces, &interfaces).unwrap());
+    }
+
+    #[test]
+    fn push_constant_range_spans_the_block_members() {
+        let path = Path::new("push_constants.spv");
+        let interface = reflect(path, &push_constant_module()).unwrap();
+        assert_eq!(interface.push_constants, Some((16, 80)));
+
+        let ranges = push_constant_ranges(std::slice::from_ref(&interface))
+            .iter()
+            .map(|range| (range.stage_flags, range.offset, range.size))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, vec![(vk::ShaderStageFlags::VERTEX, 16, 80)]);
+
+        let bundled = [reflect_bundled("vert.spv"), reflect_bundled("frag.spv")];
+        let with_push_constants = [interface, reflect_bundled("frag.spv")];
+        assert!(!same_pipeline_layout(&bundled, &with_push_constants).unwrap());
+    }
+
+    #[test]
+    fn vertex_layout_matches_the_bundled_shader() {
+        let (path, words) = bundled_shader("vert.spv");
+        let interface = reflect(&path, &words).unwrap();
+        check_vertex_inputs(&path, &interface, &Vertex::get_attribute_descriptions()).unwrap();
+    }
+
+    #[test]
+    fn vertex_layout_mismatches_are_reported() {
+        let (path, words) = bundled_shader("vert.spv");
+        let interface = reflect(&path, &words).unwrap();
+        let attributes = Vertex::get_attribute_descriptions();
+
+        let missing = attributes
+            .iter()
+            .copied()
+            .filter(|attribute| attribute.location != 4)
+            .collect::<Vec<_>>();
+        match check_vertex_inputs(&path, &interface, &missing) {
+            Err(SetupError::Shader { message, .. }) => {
+                assert!(message.contains("location 4"), "{}", message)
+            }
+            other => panic!("expected a shader error, got {:?}", other),
+        }
+
+        let mut wrong_format = attributes;
+        wrong_format[2].format = vk::Format::R32G32B32_SFLOAT;
+        match check_vertex_inputs(&path, &interface, &wrong_format) {
+            Err(SetupError::Shader { message, .. }) => {
+                assert!(message.contains("location 2"), "{}", message)
+            }
+            other => panic!("expected a shader error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn malformed_modules_are_rejected() {
+        let (path, words) = bundled_shader("vert.spv");
+        assert!(reflect(&path, &words[..HEADER_WORD_COUNT - 1]).is_err());
+        assert!(reflect(&path, &[0; 16]).is_err());
+
+        // an instruction claiming more words than the module holds
+        let mut truncated = words;
+        truncated.push((3 << 16) | OP_DECORATE);
+        assert!(reflect(&path, &truncated).is_err());
+    }
+}
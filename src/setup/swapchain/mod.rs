@@ -1,11 +1,13 @@
 use ash::{
     extensions::khr::{Surface, Swapchain},
+    version::DeviceV1_0,
     vk, Device, Instance,
 };
 use winit::dpi::PhysicalSize;
 
 pub mod utils;
 use crate::setup::devices::utils as device_utils;
+use crate::setup::error::{SetupError, SetupResult, VkResultExt};
 use crate::setup::image;
 
 pub struct SwapchainData {
@@ -15,8 +17,12 @@ pub struct SwapchainData {
     pub swapchain_image_views: Vec<vk::ImageView>,
     pub image_format: vk::Format,
     pub image_extent: vk::Extent2D,
+    pub image_usage: vk::ImageUsageFlags,
+    /// The mode actually in use, which may be a later preference or the FIFO fallback.
+    pub present_mode: vk::PresentModeKHR,
 }
 impl SwapchainData {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         instance: &Instance,
         physical_device: vk::PhysicalDevice,
@@ -24,18 +30,21 @@ impl SwapchainData {
         surface: &Surface,
         surface_khr: vk::SurfaceKHR,
         physical_window_size: PhysicalSize<u32>,
-    ) -> Self {
+        present_mode_preferences: &[vk::PresentModeKHR],
+        old_swapchain_khr: vk::SwapchainKHR,
+    ) -> SetupResult<Self> {
         let utils::SwapchainDetails {
             capabilities,
             formats,
             present_modes,
-        } = utils::query_swapchain_support(physical_device, surface, surface_khr);
+        } = utils::query_swapchain_support(physical_device, surface, surface_khr)?;
 
         let vk::SurfaceFormatKHR {
             format: image_format,
             color_space,
-        } = utils::select_swapchain_surface_format(formats);
-        let present_mode = utils::select_swapchain_present_mode(present_modes);
+        } = utils::select_swapchain_surface_format(formats)?;
+        let present_mode =
+            utils::select_swapchain_present_mode(present_modes, present_mode_preferences)?;
         let image_extent = utils::select_swapchain_extent(capabilities, physical_window_size);
 
         // 0 is a special case (== unlimited max count); otherwise, guard from max count
@@ -52,10 +61,12 @@ impl SwapchainData {
             device_utils::get_physical_device_queue_family_indices(
                 instance,
                 physical_device,
-                surface,
-                surface_khr,
-            )
-            .expect("No queue families contain required flags!");
+                Some((surface, surface_khr)),
+            )?
+            .ok_or_else(|| SetupError::unsupported("No queue families contain required flags!"))?;
+        let present = present.ok_or_else(|| {
+            SetupError::unsupported("No queue family supports presenting to the surface!")
+        })?;
 
         // enable swapchain sharing and pass relevant indices to struct iff both queue indices are the different.
         let (image_sharing_mode, queue_family_indices) = match graphics == present {
@@ -63,6 +74,10 @@ impl SwapchainData {
             false => (vk::SharingMode::CONCURRENT, vec![graphics, present]),
         };
 
+        // TRANSFER_SRC allows reading presented frames back (e.g. screenshots) where supported
+        let image_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT
+            | (capabilities.supported_usage_flags & vk::ImageUsageFlags::TRANSFER_SRC);
+
         let mut swapchain_create_info_builder = vk::SwapchainCreateInfoKHR::builder()
             .surface(surface_khr)
             .min_image_count(image_count)
@@ -70,13 +85,13 @@ impl SwapchainData {
             .image_color_space(color_space)
             .image_extent(image_extent)
             .image_array_layers(1)
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+            .image_usage(image_usage)
             .image_sharing_mode(image_sharing_mode)
             .pre_transform(capabilities.current_transform)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
             .present_mode(present_mode)
             .clipped(true)
-            .old_swapchain(vk::SwapchainKHR::null());
+            .old_swapchain(old_swapchain_khr);
 
         if !queue_family_indices.is_empty() {
             swapchain_create_info_builder =
@@ -89,31 +104,48 @@ impl SwapchainData {
         let swapchain_khr = unsafe {
             swapchain
                 .create_swapchain(&swapchain_create_info, None)
-                .expect("Failed to create swapchain!")
+                .context("Failed to create swapchain")?
         };
         let swapchain_images = unsafe {
             swapchain
                 .get_swapchain_images(swapchain_khr)
-                .expect("Failed to get swapchain images!")
+                .context("Failed to get swapchain images")?
         };
-        let swapchain_image_views = swapchain_images.iter().fold(vec![], |mut acc, image| {
-            acc.push(image::create_image_view(
-                device,
-                *image,
-                image_format,
-                vk::ImageAspectFlags::COLOR,
-                1,
-            ));
-            acc
-        });
+        let swapchain_image_views = swapchain_images
+            .iter()
+            .map(|image| {
+                image::create_image_view(
+                    device,
+                    *image,
+                    image_format,
+                    vk::ImageAspectFlags::COLOR,
+                    1,
+                )
+            })
+            .collect::<SetupResult<Vec<vk::ImageView>>>()?;
 
-        SwapchainData {
+        Ok(SwapchainData {
             swapchain,
             swapchain_khr,
             swapchain_images,
             swapchain_image_views,
             image_format,
             image_extent,
-        }
+            image_usage,
+            present_mode,
+        })
+    }
+
+    /// Destroys the image views and the swapchain.
+    ///
+    /// # Safety
+    ///
+    /// The images must no longer be in use, and the data must be dropped or replaced afterwards
+    /// without being destroyed again.
+    pub unsafe fn destroy(&self, device: &Device) {
+        self.swapchain_image_views
+            .iter()
+            .for_each(|view| device.destroy_image_view(*view, None));
+        self.swapchain.destroy_swapchain(self.swapchain_khr, None);
     }
 }
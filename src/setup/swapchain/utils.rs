@@ -1,6 +1,8 @@
 use ash::{extensions::khr::Surface, vk};
 use winit::dpi::PhysicalSize;
 
+use crate::setup::error::{SetupError, SetupResult, VkResultExt};
+
 pub struct SwapchainDetails {
     pub capabilities: vk::SurfaceCapabilitiesKHR,
     pub formats: Vec<vk::SurfaceFormatKHR>,
@@ -11,32 +13,31 @@ pub fn query_swapchain_support(
     physical_device: vk::PhysicalDevice,
     surface: &Surface,
     surface_khr: vk::SurfaceKHR,
-) -> SwapchainDetails {
+) -> SetupResult<SwapchainDetails> {
     unsafe {
         let capabilities = surface
             .get_physical_device_surface_capabilities(physical_device, surface_khr)
-            .expect("Failed to query physical device surface capabilities!");
+            .context("Failed to query physical device surface capabilities")?;
         let formats = surface
             .get_physical_device_surface_formats(physical_device, surface_khr)
-            .expect("Failed to query physical device surface formats!");
+            .context("Failed to query physical device surface formats")?;
         let present_modes = surface
             .get_physical_device_surface_present_modes(physical_device, surface_khr)
-            .expect("Failed to query physical device surface present modes!");
-        SwapchainDetails {
+            .context("Failed to query physical device surface present modes")?;
+        Ok(SwapchainDetails {
             capabilities,
             formats,
             present_modes,
-        }
+        })
     }
 }
 
 pub fn select_swapchain_surface_format(
     available_formats: Vec<vk::SurfaceFormatKHR>,
-) -> vk::SurfaceFormatKHR {
-    if available_formats.len() == 0 {
-        panic!("No swapchain surface formats available in provided vector!")
-    };
-    let first_available_format = available_formats.first().unwrap().to_owned();
+) -> SetupResult<vk::SurfaceFormatKHR> {
+    let first_available_format = available_formats.first().copied().ok_or_else(|| {
+        SetupError::unsupported("No swapchain surface formats available in provided vector!")
+    })?;
     let selected_format = available_formats.into_iter().skip(1).fold(
         first_available_format,
         |acc, available_format| {
@@ -49,27 +50,32 @@ pub fn select_swapchain_surface_format(
             }
         },
     );
-    selected_format
+    Ok(selected_format)
 }
 
+/// Uses the first of `present_mode_preferences` that is available, otherwise FIFO, which every
+/// surface supports.
 pub fn select_swapchain_present_mode(
     available_present_modes: Vec<vk::PresentModeKHR>,
-) -> vk::PresentModeKHR {
-    if available_present_modes.len() == 0 {
-        panic!("No swapchain present modes available in provided vector!")
+    present_mode_preferences: &[vk::PresentModeKHR],
+) -> SetupResult<vk::PresentModeKHR> {
+    if available_present_modes.is_empty() {
+        return Err(SetupError::unsupported(
+            "No swapchain present modes available in provided vector!",
+        ));
     };
-    if available_present_modes.contains(&vk::PresentModeKHR::MAILBOX) {
-        vk::PresentModeKHR::MAILBOX
-    } else {
-        vk::PresentModeKHR::FIFO
-    }
+    Ok(present_mode_preferences
+        .iter()
+        .copied()
+        .find(|present_mode| available_present_modes.contains(present_mode))
+        .unwrap_or(vk::PresentModeKHR::FIFO))
 }
 
 pub fn select_swapchain_extent(
     capabilities: vk::SurfaceCapabilitiesKHR,
     window_size: PhysicalSize<u32>,
 ) -> vk::Extent2D {
-    if capabilities.current_extent.width != std::u32::MAX {
+    if capabilities.current_extent.width != u32::MAX {
         capabilities.current_extent
     } else {
         let (width, height): (u32, u32) = window_size.into();
@@ -1,104 +1,138 @@
-use ash::{version::DeviceV1_0, vk, Device, Instance};
-
-use std::mem::size_of;
-
-use crate::setup::buffer;
-use crate::structs::UBO;
-
-pub fn create_descriptor_set_layout(device: &Device) -> vk::DescriptorSetLayout {
-    let bindings = [
-        vk::DescriptorSetLayoutBinding::builder()
-            .binding(0)
-            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-            .descriptor_count(1)
-            .stage_flags(vk::ShaderStageFlags::VERTEX)
-            .build(),
-        vk::DescriptorSetLayoutBinding::builder()
-            .binding(1)
-            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-            .descriptor_count(1)
-            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
-            .build(),
-    ];
+use ash::{version::DeviceV1_0, vk, Device};
+
+use std::{mem::size_of, sync::Arc};
+
+use crate::setup::allocator::Allocator;
+use crate::setup::buffer::{self, Buffer};
+use crate::setup::error::{SetupError, SetupResult, VkResultExt};
+use crate::setup::material::MaterialResources;
+use crate::structs::{MaterialFactors, UBO};
+
+/// The bindings `write_descriptor_set` fills in: the per-image UBO, the diffuse, normal and
+/// specular maps and the material factors.
+const PROVIDED_BINDINGS: [(u32, vk::DescriptorType); 5] = [
+    (0, vk::DescriptorType::UNIFORM_BUFFER),
+    (1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+    (2, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+    (3, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+    (4, vk::DescriptorType::UNIFORM_BUFFER),
+];
+
+/// Creates the layout from the bindings reflected from the shaders, see
+/// `shader_reflection::descriptor_set_layout_bindings`. Shaders may leave out any of the
+/// renderer's bindings, but every binding they declare has to be one the renderer provides.
+pub fn create_descriptor_set_layout(
+    device: &Device,
+    bindings: &[vk::DescriptorSetLayoutBinding],
+) -> SetupResult<vk::DescriptorSetLayout> {
+    for binding in bindings {
+        let provided = PROVIDED_BINDINGS
+            .iter()
+            .find(|(provided, _)| *provided == binding.binding);
+        match provided {
+            Some((_, descriptor_type))
+                if *descriptor_type == binding.descriptor_type && binding.descriptor_count == 1 => {
+            }
+            Some((_, descriptor_type)) => {
+                return Err(SetupError::unsupported(format!(
+                "Shaders expect {:?}[{}] at binding {}, but the renderer provides a single {:?}!",
+                binding.descriptor_type, binding.descriptor_count, binding.binding, descriptor_type
+            )))
+            }
+            None => {
+                return Err(SetupError::unsupported(format!(
+                    "Shaders expect {:?} at binding {}, which the renderer does not provide!",
+                    binding.descriptor_type, binding.binding
+                )))
+            }
+        }
+    }
 
     let layout_info = vk::DescriptorSetLayoutCreateInfo::builder()
-        .bindings(&bindings)
+        .bindings(bindings)
         .build();
 
     let descriptor_set_layout = unsafe {
         device
             .create_descriptor_set_layout(&layout_info, None)
-            .expect("Failed to create descriptor set layout!")
+            .context("Failed to create descriptor set layout")?
     };
-    descriptor_set_layout
+    Ok(descriptor_set_layout)
 }
 
 pub fn create(
-    instance: &Instance,
-    device: &Device,
-    physical_device: &vk::PhysicalDevice,
+    allocator: &Arc<Allocator>,
     swapchain_images: &[vk::Image],
-) -> (Vec<vk::Buffer>, Vec<vk::DeviceMemory>) {
+) -> SetupResult<Vec<Buffer>> {
     let buffer_size = size_of::<UBO>() as vk::DeviceSize;
 
-    let capacity = swapchain_images.len();
-    let mut uniform_buffers = Vec::with_capacity(capacity);
-    let mut uniform_buffers_memory = Vec::with_capacity(capacity);
-
-    for _ in 0..capacity {
-        let (buffer, memory) = buffer::create(
-            instance,
-            device,
-            physical_device,
-            buffer_size,
-            vk::BufferUsageFlags::UNIFORM_BUFFER,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-        );
-        uniform_buffers.push(buffer);
-        uniform_buffers_memory.push(memory);
-    }
-
-    (uniform_buffers, uniform_buffers_memory)
+    swapchain_images
+        .iter()
+        .map(|_| {
+            buffer::create(
+                allocator,
+                buffer_size,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            )
+        })
+        .collect()
 }
 
+/// Sized for one descriptor set per swapchain image and material, each holding `bindings`.
 pub fn create_descriptor_pool(
     device: &Device,
+    bindings: &[vk::DescriptorSetLayoutBinding],
     swapchain_images: &[vk::Image],
-) -> vk::DescriptorPool {
-    let pool_sizes = [
-        vk::DescriptorPoolSize::builder()
-            .ty(vk::DescriptorType::UNIFORM_BUFFER)
-            .descriptor_count(swapchain_images.len() as u32)
-            .build(),
-        vk::DescriptorPoolSize::builder()
-            .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-            .descriptor_count(swapchain_images.len() as u32)
-            .build(),
-    ];
+    material_count: usize,
+) -> SetupResult<vk::DescriptorPool> {
+    // a pool has to have room for at least one set, even if none is allocated from it
+    let set_count = ((swapchain_images.len() * material_count) as u32).max(1);
+    let mut pool_sizes: Vec<vk::DescriptorPoolSize> = vec![];
+    for binding in bindings {
+        let descriptor_count = set_count * binding.descriptor_count;
+        match pool_sizes
+            .iter_mut()
+            .find(|pool_size| pool_size.ty == binding.descriptor_type)
+        {
+            Some(pool_size) => pool_size.descriptor_count += descriptor_count,
+            None => pool_sizes.push(
+                vk::DescriptorPoolSize::builder()
+                    .ty(binding.descriptor_type)
+                    .descriptor_count(descriptor_count)
+                    .build(),
+            ),
+        }
+    }
 
     let pool_info = vk::DescriptorPoolCreateInfo::builder()
         .pool_sizes(&pool_sizes)
-        .max_sets(swapchain_images.len() as u32)
+        .max_sets(set_count)
         .build();
 
     let descriptor_pool = unsafe {
         device
             .create_descriptor_pool(&pool_info, None)
-            .expect("Failed to create descriptor pool!")
+            .context("Failed to create descriptor pool")?
     };
-    descriptor_pool
+    Ok(descriptor_pool)
 }
 
+/// Allocates one descriptor set per swapchain image and material, indexed `[image][material]`.
 pub fn create_descriptor_sets(
     device: &Device,
     descriptor_pool: vk::DescriptorPool,
     descriptor_set_layout: vk::DescriptorSetLayout,
-    uniform_buffers: &[vk::Buffer],
+    bindings: &[vk::DescriptorSetLayoutBinding],
+    uniform_buffers: &[Buffer],
     swapchain_images: &[vk::Image],
-    image_view: vk::ImageView,
-    sampler: vk::Sampler,
-) -> Vec<vk::DescriptorSet> {
-    let layouts = vec![descriptor_set_layout; swapchain_images.len()];
+    materials: &[MaterialResources],
+) -> SetupResult<Vec<Vec<vk::DescriptorSet>>> {
+    // allocating no sets is invalid, and `chunks` needs a non-zero size
+    if materials.is_empty() {
+        return Ok(vec![vec![]; swapchain_images.len()]);
+    }
+    let layouts = vec![descriptor_set_layout; swapchain_images.len() * materials.len()];
 
     let alloc_info = vk::DescriptorSetAllocateInfo::builder()
         .descriptor_pool(descriptor_pool)
@@ -108,46 +142,95 @@ pub fn create_descriptor_sets(
     let descriptor_sets = unsafe {
         device
             .allocate_descriptor_sets(&alloc_info)
-            .expect("Failed to allocate descriptor sets!")
+            .context("Failed to allocate descriptor sets")?
     };
 
-    descriptor_sets
-        .iter()
+    let descriptor_sets = descriptor_sets
+        .chunks(materials.len())
         .zip(uniform_buffers)
-        .for_each(|(descriptor_set, uniform_buffer)| {
-            let buffer_info = vk::DescriptorBufferInfo::builder()
-                .buffer(*uniform_buffer)
-                .offset(0)
-                .range(size_of::<UBO>() as u64)
-                .build();
-
-            let image_info = vk::DescriptorImageInfo::builder()
-                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                .image_view(image_view)
-                .sampler(sampler)
-                .build();
-
-            let descriptor_writes = [
-                vk::WriteDescriptorSet::builder()
-                    .dst_set(*descriptor_set)
-                    .dst_binding(0)
-                    .dst_array_element(0)
-                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-                    .buffer_info(&[buffer_info])
-                    .build(),
-                vk::WriteDescriptorSet::builder()
-                    .dst_set(*descriptor_set)
-                    .dst_binding(1)
-                    .dst_array_element(0)
-                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .image_info(&[image_info])
-                    .build(),
-            ];
-
-            unsafe {
-                device.update_descriptor_sets(&descriptor_writes, &[]);
-            }
-        });
+        .map(|(image_descriptor_sets, uniform_buffer)| {
+            image_descriptor_sets
+                .iter()
+                .zip(materials)
+                .for_each(|(descriptor_set, material)| {
+                    write_descriptor_set(
+                        device,
+                        *descriptor_set,
+                        bindings,
+                        uniform_buffer,
+                        material,
+                    )
+                });
+            image_descriptor_sets.to_vec()
+        })
+        .collect();
+
+    Ok(descriptor_sets)
+}
 
-    descriptor_sets
+fn write_descriptor_set(
+    device: &Device,
+    descriptor_set: vk::DescriptorSet,
+    bindings: &[vk::DescriptorSetLayoutBinding],
+    uniform_buffer: &Buffer,
+    material: &MaterialResources,
+) {
+    let buffer_info = [vk::DescriptorBufferInfo::builder()
+        .buffer(uniform_buffer.buffer)
+        .offset(0)
+        .range(size_of::<UBO>() as u64)
+        .build()];
+    let factors_info = [vk::DescriptorBufferInfo::builder()
+        .buffer(material.factors.buffer)
+        .offset(0)
+        .range(size_of::<MaterialFactors>() as u64)
+        .build()];
+    let image_infos = [&material.diffuse, &material.normal, &material.specular]
+        .iter()
+        .map(|texture| {
+            [vk::DescriptorImageInfo::builder()
+                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .image_view(texture.image.view)
+                .sampler(texture.sampler)
+                .build()]
+        })
+        .collect::<Vec<[vk::DescriptorImageInfo; 1]>>();
+
+    let mut descriptor_writes = vec![
+        vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(&buffer_info)
+            .build(),
+        vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(4)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(&factors_info)
+            .build(),
+    ];
+    // bindings 1 to 3 hold the diffuse, normal and specular maps
+    descriptor_writes.extend(image_infos.iter().zip(1..).map(|(image_info, binding)| {
+        vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(binding)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(image_info)
+            .build()
+    }));
+
+    // shaders are free to leave out bindings, which must not be written then
+    descriptor_writes.retain(|write| {
+        bindings
+            .iter()
+            .any(|binding| binding.binding == write.dst_binding)
+    });
+
+    unsafe {
+        device.update_descriptor_sets(&descriptor_writes, &[]);
+    }
 }
@@ -3,15 +3,17 @@ use ash::{
     vk, Entry, Instance,
 };
 
+use crate::setup::error::{SetupResult, VkResultExt};
+
 pub mod utils;
 
 pub fn initialize(
     entry: &Entry,
     instance: &Instance,
     enable_validation_layers: bool,
-) -> (Option<DebugUtils>, Option<vk::DebugUtilsMessengerEXT>) {
+) -> SetupResult<(Option<DebugUtils>, Option<vk::DebugUtilsMessengerEXT>)> {
     if !enable_validation_layers {
-        return (None, None);
+        return Ok((None, None));
     }
 
     let debug_utils = DebugUtils::new(entry, instance);
@@ -20,7 +22,7 @@ pub fn initialize(
     unsafe {
         let debug_utils_messenger_ext =
             DebugUtils::create_debug_utils_messenger(&debug_utils, &create_info, None)
-                .expect("Failed to create DebugUtilsMessengerEXT");
-        (Some(debug_utils), Some(debug_utils_messenger_ext))
+                .context("Failed to create DebugUtilsMessengerEXT")?;
+        Ok((Some(debug_utils), Some(debug_utils_messenger_ext)))
     }
 }
\ No newline at end of file
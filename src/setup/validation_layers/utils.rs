@@ -5,23 +5,21 @@ use std::{
 
 use ash::{version::EntryV1_0, vk, Entry};
 
+use crate::setup::error::{SetupResult, VkResultExt};
+
 const VALIDATION_LAYERS: [&str; 1] = ["VK_LAYER_KHRONOS_validation"];
 
-pub fn check_validation_layer_support(entry: &Entry) -> bool {
-    let layer_properties = match entry.enumerate_instance_layer_properties() {
-        Ok(layer_properties) => layer_properties,
-        Err(e) => panic!("Failed to enumerate instance layer properties: {}", e),
-    };
+pub fn check_validation_layer_support(entry: &Entry) -> SetupResult<bool> {
+    let layer_properties = entry
+        .enumerate_instance_layer_properties()
+        .context("Failed to enumerate instance layer properties")?;
     let layer_names = layer_properties
         .iter()
-        .map(|lp| unsafe { CStr::from_ptr(lp.layer_name.as_ptr()).to_str().unwrap() })
-        .collect::<Vec<&str>>();
-    for validation_layer in VALIDATION_LAYERS.iter() {
-        if !layer_names.contains(validation_layer) {
-            return false;
-        }
-    }
-    true
+        .map(|lp| unsafe { CStr::from_ptr(lp.layer_name.as_ptr()).to_string_lossy() })
+        .collect::<Vec<_>>();
+    Ok(VALIDATION_LAYERS
+        .iter()
+        .all(|validation_layer| layer_names.iter().any(|name| name == validation_layer)))
 }
 
 pub fn get_enabled_layer_names() -> Vec<CString> {
@@ -56,9 +54,7 @@ unsafe extern "system" fn pfn_user_callback(
 ) -> vk::Bool32 {
     println!(
         "validation layer: {}",
-        CStr::from_ptr((*p_callback_data).p_message)
-            .to_str()
-            .unwrap()
+        CStr::from_ptr((*p_callback_data).p_message).to_string_lossy()
     );
     vk::FALSE
 }
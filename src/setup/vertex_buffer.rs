@@ -1,35 +1,23 @@
-use ash::{
-    version::DeviceV1_0,
-    Instance,
-    Device,
-    vk,
-};
+use ash::vk;
 
 use std::{
-    mem::size_of,
-    ptr::copy_nonoverlapping
+    mem::size_of_val,
+    sync::Arc
 };
 
 use crate::structs::Vertex;
-use crate::setup::buffer;
+use crate::setup::allocator::Allocator;
+use crate::setup::buffer::{self, Buffer};
+use crate::setup::error::SetupResult;
+use crate::setup::queue::Queue;
 
-pub fn create(instance: &Instance, physical_device: &vk::PhysicalDevice, device: &Device, command_pool: vk::CommandPool, queue: vk::Queue, vertices: &[Vertex]) -> (vk::Buffer, vk::DeviceMemory) {
-    let buffer_size = (size_of::<Vertex>() * vertices.len()) as vk::DeviceSize;
-    let (staging_buffer, staging_buffer_memory) = buffer::create(instance, device, physical_device, buffer_size, vk::BufferUsageFlags::TRANSFER_SRC, vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT);
-   
-    unsafe {
-        let data_ptr = device.map_memory(staging_buffer_memory, 0, buffer_size, vk::MemoryMapFlags::empty()).expect("Failed to map vertex buffer memory!");
-        copy_nonoverlapping(vertices.as_ptr(), data_ptr as *mut Vertex, vertices.len());
-        device.unmap_memory(staging_buffer_memory);
-    };
+pub fn create(allocator: &Arc<Allocator>, command_pool: vk::CommandPool, queue: &Queue, vertices: &[Vertex]) -> SetupResult<Buffer> {
+    let buffer_size = size_of_val(vertices) as vk::DeviceSize;
+    let staging_buffer = buffer::create(allocator, buffer_size, vk::BufferUsageFlags::TRANSFER_SRC, vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT)?;
+    staging_buffer.write(vertices)?;
 
-    let (vertex_buffer, vertex_buffer_memory) = buffer::create(instance, device, physical_device, buffer_size, vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER, vk::MemoryPropertyFlags::DEVICE_LOCAL);
-    buffer::copy(device, command_pool, queue, staging_buffer, vertex_buffer, buffer_size);
+    let vertex_buffer = buffer::create(allocator, buffer_size, vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER, vk::MemoryPropertyFlags::DEVICE_LOCAL)?;
+    buffer::copy(allocator.device(), command_pool, queue, staging_buffer.buffer, vertex_buffer.buffer, buffer_size)?;
 
-    unsafe {
-        device.destroy_buffer(staging_buffer, None);
-        device.free_memory(staging_buffer_memory, None);
-    }
-    
-    (vertex_buffer, vertex_buffer_memory)
+    Ok(vertex_buffer)
 }
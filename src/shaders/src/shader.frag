@@ -3,12 +3,74 @@
 
 layout(location = 0) in vec3 fragColor;
 layout(location = 1) in vec2 fragUv;
+layout(location = 2) in vec3 fragNormal;
+layout(location = 3) in vec3 fragPosition;
+layout(location = 4) in vec3 fragCameraPosition;
+// the bitangent sign in w; zero for models without tangents
+layout(location = 5) in vec4 fragTangent;
 
 layout(binding = 1) uniform sampler2D texSampler;
+layout(binding = 2) uniform sampler2D normalSampler;
+layout(binding = 3) uniform sampler2D specularSampler;
+
+layout(binding = 4) uniform MaterialFactors {
+    vec4 diffuse;
+    // the specular color in xyz and the shininess in w
+    vec4 specular;
+} material;
 
 layout(location = 0) out vec4 outColor;
 
+// world space direction towards the light
+const vec3 lightDirection = vec3(0.408248, 0.408248, 0.816497);
+const float ambient = 0.2;
+
+// Tangent frame from the screen space derivatives of position and uv, so normal maps work on
+// models that come without tangents.
+mat3 cotangentFrame(vec3 normal) {
+    vec3 dp1 = dFdx(fragPosition);
+    vec3 dp2 = dFdy(fragPosition);
+    vec2 duv1 = dFdx(fragUv);
+    vec2 duv2 = dFdy(fragUv);
+
+    vec3 dp2perp = cross(dp2, normal);
+    vec3 dp1perp = cross(normal, dp1);
+    vec3 tangent = dp2perp * duv1.x + dp1perp * duv2.x;
+    vec3 bitangent = dp2perp * duv1.y + dp1perp * duv2.y;
+    float scale = inversesqrt(max(max(dot(tangent, tangent), dot(bitangent, bitangent)), 1e-20));
+    return mat3(tangent * scale, bitangent * scale, normal);
+}
+
+mat3 tangentFrame(vec3 normal) {
+    // derivatives are only defined in uniform control flow, so this is computed either way
+    mat3 derivedFrame = cotangentFrame(normal);
+    if (dot(fragTangent.xyz, fragTangent.xyz) == 0.0) {
+        return derivedFrame;
+    }
+    vec3 tangent = normalize(fragTangent.xyz - normal * dot(normal, fragTangent.xyz));
+    vec3 bitangent = cross(normal, tangent) * fragTangent.w;
+    return mat3(tangent, bitangent, normal);
+}
+
 void main() {
-    outColor = texture(texSampler, fragUv);
-    //outColor = vec4(fragUv, 0.0, 1.0);
-}
\ No newline at end of file
+    vec4 diffuse = texture(texSampler, fragUv) * material.diffuse;
+    vec3 mappedNormal = texture(normalSampler, fragUv).xyz * 2.0 - 1.0;
+    vec3 specularColor = texture(specularSampler, fragUv).rgb * material.specular.rgb;
+
+    // generated normals are zero on degenerate faces, which then only get the ambient term
+    vec3 vertexNormal = dot(fragNormal, fragNormal) > 0.0 ? normalize(fragNormal) : vec3(0.0);
+    vec3 normal = tangentFrame(vertexNormal) * mappedNormal;
+    normal = dot(normal, normal) > 0.0 ? normalize(normal) : vec3(0.0);
+
+    float lambert = max(dot(normal, lightDirection), 0.0);
+    vec3 color = diffuse.rgb * (ambient + (1.0 - ambient) * lambert);
+
+    // Blinn-Phong highlight; a shininess of zero means the material has none
+    vec3 viewDirection = normalize(fragCameraPosition - fragPosition);
+    vec3 halfway = normalize(lightDirection + viewDirection);
+    float highlight = pow(max(dot(normal, halfway), 0.0), max(material.specular.w, 1.0));
+    if (lambert > 0.0 && material.specular.w > 0.0) {
+        color += specularColor * highlight;
+    }
+    outColor = vec4(color, diffuse.a);
+}
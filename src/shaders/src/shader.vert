@@ -7,15 +7,35 @@ layout(binding = 0) uniform UniformBufferObject {
     mat4 proj;
 } ubo;
 
+// where this draw places its copy of the model, before the model matrix applies
+layout(push_constant) uniform ObjectConstants {
+    mat4 transform;
+} object;
+
 layout(location = 0) in vec3 inPosition;
 layout(location = 1) in vec3 inColor;
 layout(location = 2) in vec2 inUv;
+layout(location = 3) in vec3 inNormal;
+layout(location = 4) in vec4 inTangent;
 
 layout(location = 0) out vec3 fragColor;
 layout(location = 1) out vec2 fragUv;
+layout(location = 2) out vec3 fragNormal;
+layout(location = 3) out vec3 fragPosition;
+layout(location = 4) out vec3 fragCameraPosition;
+layout(location = 5) out vec4 fragTangent;
 
 void main() {
-    gl_Position = ubo.proj * ubo.view * ubo.model * vec4(inPosition, 1.0);
+    mat4 model = ubo.model * object.transform;
+    vec4 worldPosition = model * vec4(inPosition, 1.0);
+    gl_Position = ubo.proj * ubo.view * worldPosition;
     fragColor = inColor;
     fragUv = inUv;
-}
\ No newline at end of file
+    // both matrices only rotate, translate and scale uniformly, so they transform normals as they
+    // are; the fragment shader normalizes them again
+    fragNormal = mat3(model) * inNormal;
+    fragTangent = vec4(mat3(model) * inTangent.xyz, inTangent.w);
+    fragPosition = worldPosition.xyz;
+    // the view matrix is a rotation and a translation, so its inverse is the transposed rotation
+    fragCameraPosition = -(transpose(mat3(ubo.view)) * ubo.view[3].xyz);
+}
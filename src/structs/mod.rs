@@ -1,7 +1,7 @@
-use std::mem::size_of;
+use std::{fmt, mem::size_of, path::PathBuf, sync::Arc};
 
 use ash::vk;
-use cgmath::{Matrix4, Vector2, Vector3};
+use cgmath::{Matrix4, Vector2, Vector3, Vector4};
 
 use field_offset::offset_of;
 
@@ -11,18 +11,44 @@ pub struct Vertex {
     pub position: Vector3<f32>,
     pub color: Vector3<f32>,
     pub uv: Vector2<f32>,
+    pub normal: Vector3<f32>,
+    /// Tangent direction in `xyz` and the bitangent sign in `w`; zero when the model has none.
+    pub tangent: Vector4<f32>,
 }
 
 impl Vertex {
-    #[allow(dead_code)]
+    #[allow(dead_code, clippy::too_many_arguments)]
     pub fn new(x: f32, y: f32, z: f32, r: f32, g: f32, b: f32, u: f32, v: f32) -> Self {
         Self {
             position: Vector3 { x, y, z },
             color: Vector3 { x: r, y: g, z: b },
             uv: Vector2 { x: u, y: v },
+            normal: Vector3::unit_z(),
+            tangent: Vector4::new(1.0, 0.0, 0.0, 1.0),
         }
     }
 
+    /// Bit pattern of every attribute, for deduplicating identical vertices in a hash map.
+    pub fn hash_key(&self) -> [u32; 15] {
+        [
+            self.position.x.to_bits(),
+            self.position.y.to_bits(),
+            self.position.z.to_bits(),
+            self.color.x.to_bits(),
+            self.color.y.to_bits(),
+            self.color.z.to_bits(),
+            self.uv.x.to_bits(),
+            self.uv.y.to_bits(),
+            self.normal.x.to_bits(),
+            self.normal.y.to_bits(),
+            self.normal.z.to_bits(),
+            self.tangent.x.to_bits(),
+            self.tangent.y.to_bits(),
+            self.tangent.z.to_bits(),
+            self.tangent.w.to_bits(),
+        ]
+    }
+
     pub fn get_binding_description() -> Vec<vk::VertexInputBindingDescription> {
         vec![vk::VertexInputBindingDescription::builder()
             .binding(0)
@@ -51,12 +77,114 @@ impl Vertex {
                 .format(vk::Format::R32G32_SFLOAT)
                 .offset(offset_of!(Vertex => uv).get_byte_offset() as u32)
                 .build(),
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(3)
+                .format(vk::Format::R32G32B32_SFLOAT)
+                .offset(offset_of!(Vertex => normal).get_byte_offset() as u32)
+                .build(),
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(4)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset(offset_of!(Vertex => tangent).get_byte_offset() as u32)
+                .build(),
         ]
     }
 }
 
+/// A range of the shared index buffer drawn with a single `cmd_draw_indexed`.
+#[derive(Clone, Debug, Copy)]
+pub struct SubMesh {
+    pub first_index: u32,
+    pub index_count: u32,
+    /// Added to every index of the range; the indices stay local to the sub-mesh's vertices.
+    pub vertex_offset: i32,
+    /// Index into `Mesh::materials`.
+    pub material_id: usize,
+}
+
+/// Where the pixels of a material texture come from.
+#[derive(Clone)]
+pub enum TextureSource {
+    File(PathBuf),
+    /// Decoded RGBA8 pixels, e.g. an image embedded in a glTF buffer.
+    Rgba {
+        width: u32,
+        height: u32,
+        pixels: Arc<Vec<u8>>,
+    },
+}
+
+impl fmt::Debug for TextureSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureSource::File(path) => f.debug_tuple("File").field(path).finish(),
+            TextureSource::Rgba { width, height, .. } => f
+                .debug_struct("Rgba")
+                .field("width", width)
+                .field("height", height)
+                .finish(),
+        }
+    }
+}
+
+/// Surface description of a sub-mesh; textures that are not set fall back to neutral ones.
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    pub diffuse_color: Vector3<f32>,
+    pub specular_color: Vector3<f32>,
+    pub shininess: f32,
+    pub diffuse_texture: Option<TextureSource>,
+    pub normal_texture: Option<TextureSource>,
+    pub specular_texture: Option<TextureSource>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: String::from("default"),
+            diffuse_color: Vector3::new(1.0, 1.0, 1.0),
+            specular_color: Vector3::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            diffuse_texture: None,
+            normal_texture: None,
+            specular_texture: None,
+        }
+    }
+}
+
+/// Color factors of a `Material` as laid out in its uniform buffer (std140).
+#[repr(C)]
+#[derive(Clone, Debug, Copy)]
+pub struct MaterialFactors {
+    pub diffuse: Vector4<f32>,
+    /// The specular color in `xyz` and the shininess in `w`.
+    pub specular: Vector4<f32>,
+}
+
+impl From<&Material> for MaterialFactors {
+    fn from(material: &Material) -> Self {
+        Self {
+            diffuse: material.diffuse_color.extend(1.0),
+            specular: material.specular_color.extend(material.shininess),
+        }
+    }
+}
+
+/// Vertices and indices of every sub-mesh of a model, packed into single buffers.
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub sub_meshes: Vec<SubMesh>,
+    pub materials: Vec<Material>,
+}
+
 #[repr(C)]
 #[derive(Clone, Debug, Copy)]
+#[allow(clippy::upper_case_acronyms)]
 pub struct UBO {
     pub model: Matrix4<f32>,
     pub view: Matrix4<f32>,
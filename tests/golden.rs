@@ -0,0 +1,167 @@
+//! Golden-image regression tests: the viking room is rendered headless at a fixed time, camera and
+//! MSAA level, read back and compared against the reference PNGs in `tests/golden/`.
+//!
+//! Run with `GOLDEN_UPDATE=1` to record the references from the current render; otherwise a missing
+//! reference fails the test. References are recorded on lavapipe, see the README. The tests are skipped when no Vulkan implementation is available.
+
+use std::{env, fs, path::Path};
+
+use ash::{
+    version::{EntryV1_0, InstanceV1_0},
+    vk, Entry,
+};
+use image::{Rgba, RgbaImage};
+
+use ash_survival_kit::{
+    app::{FrameClock, VulkanApp},
+    config::{Config, GraphicsConfig},
+};
+
+const IMAGE_EXTENT: vk::Extent2D = vk::Extent2D {
+    width: 400,
+    height: 300,
+};
+// 4x is guaranteed for color and depth attachments by the spec
+const MSAA_SAMPLES: u32 = 4;
+
+/// Largest difference allowed per color channel before a pixel counts as mismatched.
+const PIXEL_TOLERANCE: u8 = 8;
+/// Fraction of mismatched pixels tolerated, absorbing rasterization differences between drivers.
+const MAX_MISMATCHED_RATIO: f64 = 0.001;
+
+fn vulkan_available() -> bool {
+    let entry = match Entry::new() {
+        Ok(entry) => entry,
+        Err(_) => return false,
+    };
+    let create_info = vk::InstanceCreateInfo::builder().build();
+    let instance = match unsafe { entry.create_instance(&create_info, None) } {
+        Ok(instance) => instance,
+        Err(_) => return false,
+    };
+    let has_physical_devices = unsafe { instance.enumerate_physical_devices() }
+        .map(|physical_devices| !physical_devices.is_empty())
+        .unwrap_or(false);
+    unsafe { instance.destroy_instance(None) };
+    has_physical_devices
+}
+
+fn render_at(elapsed_seconds: f32) -> RgbaImage {
+    let config = Config {
+        graphics: GraphicsConfig {
+            msaa_samples: MSAA_SAMPLES,
+            validation: false,
+            pipeline_cache: None,
+            ..GraphicsConfig::default()
+        },
+        ..Config::default()
+    };
+    let mut app = VulkanApp::new_headless(IMAGE_EXTENT, &config)
+        .expect("Failed to set up headless renderer!");
+    assert!(
+        app.clamped_settings()
+            .iter()
+            .all(|clamped_setting| clamped_setting.name != "msaa_samples"),
+        "MSAA sample count was clamped!"
+    );
+    let image_index = app
+        .run_headless(1, &FrameClock::Fixed(elapsed_seconds))
+        .expect("Failed to render headless frame!");
+    app.read_back_frame(image_index)
+        .expect("Failed to read back frame!")
+}
+
+fn max_channel_difference(a: &Rgba<u8>, b: &Rgba<u8>) -> u8 {
+    a.0.iter()
+        .zip(b.0.iter())
+        .map(|(a, b)| (*a as i16 - *b as i16).unsigned_abs() as u8)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Mismatched pixels are painted red on top of a dimmed grayscale copy of the actual image.
+fn build_diff_image(actual: &RgbaImage, reference: &RgbaImage) -> (RgbaImage, usize) {
+    let mut mismatched_pixels = 0;
+    let diff = RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let actual_pixel = actual.get_pixel(x, y);
+        if max_channel_difference(actual_pixel, reference.get_pixel(x, y)) > PIXEL_TOLERANCE {
+            mismatched_pixels += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, _] = actual_pixel.0;
+            let luma = ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 4000) as u8;
+            Rgba([luma, luma, luma, 255])
+        }
+    });
+    (diff, mismatched_pixels)
+}
+
+fn assert_matches_golden(name: &str, actual: &RgbaImage) {
+    let reference_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let reference_path = reference_dir.join(format!("{}.png", name));
+
+    if env::var_os("GOLDEN_UPDATE").is_some_and(|update| update == "1") {
+        fs::create_dir_all(&reference_dir).expect("Failed to create golden image directory!");
+        actual
+            .save(&reference_path)
+            .expect("Failed to write golden image!");
+        eprintln!("Recorded golden image {}", reference_path.display());
+        return;
+    }
+    assert!(
+        reference_path.exists(),
+        "{}: no golden image at {}; record it with GOLDEN_UPDATE=1",
+        name,
+        reference_path.display()
+    );
+
+    let reference = image::open(&reference_path)
+        .expect("Failed to load golden image!")
+        .to_rgba();
+    assert_eq!(
+        reference.dimensions(),
+        actual.dimensions(),
+        "{}: rendered size differs from the golden image",
+        name
+    );
+
+    let (diff, mismatched_pixels) = build_diff_image(actual, &reference);
+    let pixel_count = (actual.width() * actual.height()) as f64;
+    if mismatched_pixels as f64 / pixel_count > MAX_MISMATCHED_RATIO {
+        let output_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+        fs::create_dir_all(&output_dir).expect("Failed to create golden output directory!");
+        let actual_path = output_dir.join(format!("{}.actual.png", name));
+        let diff_path = output_dir.join(format!("{}.diff.png", name));
+        actual
+            .save(&actual_path)
+            .expect("Failed to write actual image!");
+        diff.save(&diff_path).expect("Failed to write diff image!");
+        panic!(
+            "{}: {} of {} pixels differ by more than {}; see {} and {}",
+            name,
+            mismatched_pixels,
+            pixel_count,
+            PIXEL_TOLERANCE,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+fn viking_room_at_rest() {
+    if !vulkan_available() {
+        eprintln!("Skipping golden image test: no Vulkan implementation available");
+        return;
+    }
+    assert_matches_golden("viking_room_at_rest", &render_at(0.0));
+}
+
+#[test]
+fn viking_room_rotated() {
+    if !vulkan_available() {
+        eprintln!("Skipping golden image test: no Vulkan implementation available");
+        return;
+    }
+    assert_matches_golden("viking_room_rotated", &render_at(3.0));
+}